@@ -0,0 +1,384 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::ai::{AILevel, Evaluation, ScoreTable};
+use crate::codingame::CodinGameBot;
+use crate::external::ExternalEngine;
+use crate::heuristic::Profile;
+use crate::morpion::{format_move, Morpion};
+use crate::skill::RATED_SKILLS;
+
+/// File listing the external engines that can play, when it exists.
+pub const ENGINES_FILE: &str = "engines.txt";
+
+static ENGINES: OnceLock<Vec<EngineConfig>> = OnceLock::new();
+/// Heuristic profiles loaded from files to play as agents, with their path.
+static PROFILES: Mutex<Vec<(String, Profile)>> = Mutex::new(Vec::new());
+
+/// Reasons why an agent loses the game without finishing it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AgentError {
+    /// The program could not be launched, or it exited.
+    Crashed(String),
+    /// The program did not answer in time.
+    Timeout(Duration),
+    /// The program played a move that is not valid in the position.
+    IllegalMove(String),
+    /// The program sent something that does not follow the engine protocol.
+    Protocol(String),
+}
+
+impl std::fmt::Display for AgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentError::Crashed(reason) => write!(f, "crashed ({})", reason),
+            AgentError::Timeout(limit) => write!(f, "no move within {} ms", limit.as_millis()),
+            AgentError::IllegalMove(m) => write!(f, "illegal move {}", m),
+            AgentError::Protocol(line) => write!(f, "protocol error: {}", line),
+        }
+    }
+}
+
+impl AgentError {
+    /// Reads an error written with its [`std::fmt::Display`] implementation.
+    /// Returns `None` if the input string does not match any error.
+    pub fn from_str(s: &str) -> Option<Self> {
+        if let Some(reason) = s
+            .strip_prefix("crashed (")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            Some(AgentError::Crashed(reason.to_string()))
+        } else if let Some(limit) = s
+            .strip_prefix("no move within ")
+            .and_then(|s| s.strip_suffix(" ms"))
+        {
+            limit
+                .parse()
+                .ok()
+                .map(|limit| AgentError::Timeout(Duration::from_millis(limit)))
+        } else if let Some(m) = s.strip_prefix("illegal move ") {
+            Some(AgentError::IllegalMove(m.to_string()))
+        } else {
+            s.strip_prefix("protocol error: ")
+                .map(|line| AgentError::Protocol(line.to_string()))
+        }
+    }
+}
+
+/// Something that chooses moves: a built-in AI level or an external program.
+pub trait Agent {
+    /// Chooses a move `(ult_index, index)` for the player to move.
+    /// Returns `Ok(None)` if `stop` was set before a move was found.
+    fn play(
+        &mut self,
+        morpion: &Morpion,
+        stop: &AtomicBool,
+    ) -> Result<Option<(usize, usize)>, AgentError>;
+
+    /// Evaluation of the position that led to the last move played, if the agent gave one.
+    fn evaluation(&self) -> Option<Evaluation> {
+        None
+    }
+
+    /// Tells if the agent plays under the time controls of the fights: only external programs do.
+    /// The built-in AI levels and profiles search to their fixed depth however long it takes.
+    fn is_timed(&self) -> bool {
+        false
+    }
+
+    /// Gives the agent the time it may think on its next moves. Ignored by the agents that can't adapt.
+    fn set_movetime(&mut self, _movetime: Duration) {}
+}
+
+/// A built-in AI level.
+pub struct LevelAgent {
+    level: AILevel,
+    /// Scores found by the searches of the level, reused by its next moves.
+    table: ScoreTable,
+    evaluation: Option<Evaluation>,
+}
+
+impl LevelAgent {
+    pub fn new(level: AILevel) -> Self {
+        Self {
+            level,
+            table: ScoreTable::new(),
+            evaluation: None,
+        }
+    }
+}
+
+impl Agent for LevelAgent {
+    fn play(
+        &mut self,
+        morpion: &Morpion,
+        stop: &AtomicBool,
+    ) -> Result<Option<(usize, usize)>, AgentError> {
+        let Some((best_move, evaluation)) = morpion.ai_choice(self.level, &mut self.table, stop)
+        else {
+            return Ok(None);
+        };
+        self.evaluation = evaluation;
+        Ok(Some(best_move))
+    }
+
+    fn evaluation(&self) -> Option<Evaluation> {
+        self.evaluation.clone()
+    }
+}
+
+/// A heuristic profile playing like the `custom` AI level, without being the loaded one.
+struct ProfileAgent {
+    profile: Profile,
+    /// Scores found by the searches of the profile, reused by its next moves.
+    table: ScoreTable,
+    evaluation: Option<Evaluation>,
+}
+
+impl Agent for ProfileAgent {
+    fn play(
+        &mut self,
+        morpion: &Morpion,
+        stop: &AtomicBool,
+    ) -> Result<Option<(usize, usize)>, AgentError> {
+        let heuristic = |node: &Morpion, player| self.profile.heuristic.evaluate(node, player);
+        // Profiles play like the custom level, with other parameters
+        let Some((best_move, evaluation)) = morpion.choice_with(
+            self.profile.depth,
+            &heuristic,
+            AILevel::Custom.aids(),
+            &mut self.table,
+            stop,
+        ) else {
+            return Ok(None);
+        };
+        self.evaluation = evaluation;
+        Ok(Some(best_move))
+    }
+
+    fn evaluation(&self) -> Option<Evaluation> {
+        self.evaluation.clone()
+    }
+}
+
+/// Asks the agent for its move, checking that it is valid.
+/// Returns `Ok(None)` if `stop` was set before a move was found.
+pub fn checked_move(
+    agent: &mut dyn Agent,
+    morpion: &Morpion,
+    stop: &AtomicBool,
+) -> Result<Option<(usize, usize)>, AgentError> {
+    let Some((ult_index, index)) = agent.play(morpion, stop)? else {
+        return Ok(None);
+    };
+    if ult_index >= 9 || index >= 9 || !morpion.index_is_playable(ult_index, index) {
+        return Err(AgentError::IllegalMove(format_move((ult_index, index))));
+    }
+    Ok(Some((ult_index, index)))
+}
+
+/// Asks the agent for its move and plays it, checking that it is valid.
+/// Returns `Ok(None)` if `stop` was set before a move was found.
+pub fn agent_move(
+    agent: &mut dyn Agent,
+    morpion: &Morpion,
+    stop: &AtomicBool,
+) -> Result<Option<Morpion>, AgentError> {
+    let Some((ult_index, index)) = checked_move(agent, morpion, stop)? else {
+        return Ok(None);
+    };
+    let mut child = morpion.clone();
+    child.play_at(ult_index, index);
+    Ok(Some(child))
+}
+
+/// Protocol spoken by an external engine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    /// The engine protocol of [`crate::engine`].
+    Engine,
+    /// The protocol of the _Ultimate Tic-Tac-Toe_ bots of CodinGame (see [`crate::codingame`]).
+    CodinGame,
+}
+
+/// An external engine: a program speaking the engine protocol (see [`crate::engine`]) or the CodinGame one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EngineConfig {
+    pub name: String,
+    pub protocol: Protocol,
+    /// Thinking time given for each move, the engine loses if it does not answer in time.
+    pub movetime: Duration,
+    /// The program and its arguments.
+    pub command: Vec<String>,
+}
+
+/// Parses the list of external engines: one `name movetime_ms [--codingame] command [args...]` line per engine,
+/// lines starting with `#` are ignored.
+pub fn parse_engines(text: &str) -> Result<Vec<EngineConfig>, String> {
+    let mut engines = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [name, movetime, ref command @ ..] = fields[..] else {
+            return Err(format!("invalid line: {}", line));
+        };
+        let (protocol, command) = match command {
+            ["--codingame", command @ ..] => (Protocol::CodinGame, command),
+            _ => (Protocol::Engine, command),
+        };
+        if command.is_empty() {
+            return Err(format!("missing command for {}", name));
+        }
+        if AILevel::from_str(name).is_some() {
+            return Err(format!("{} is the name of an AI level", name));
+        }
+        let movetime = movetime
+            .parse::<u64>()
+            .map_err(|e| format!("invalid time for {}: {}", name, e))?;
+        engines.push(EngineConfig {
+            name: name.to_string(),
+            protocol,
+            movetime: Duration::from_millis(movetime),
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+        });
+    }
+    Ok(engines)
+}
+
+/// Returns the external engines that can play.
+/// They are loaded from [`ENGINES_FILE`] the first time, falling back to none.
+pub fn engines() -> &'static [EngineConfig] {
+    ENGINES.get_or_init(|| {
+        let text = match fs::read_to_string(Path::new(ENGINES_FILE)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                println!("can't load {}: {}", ENGINES_FILE, e);
+                return Vec::new();
+            }
+        };
+        parse_engines(&text).unwrap_or_else(|e| {
+            println!("can't load {}: {}", ENGINES_FILE, e);
+            Vec::new()
+        })
+    })
+}
+
+/// Loads a heuristic profile file so it can play, each path being loaded once.
+/// Returns its index in [`PROFILES`], or `None` if it can't be loaded.
+fn load_profile(path: &str) -> Option<usize> {
+    let mut profiles = PROFILES.lock().unwrap();
+    if let Some(index) = profiles.iter().position(|(name, _)| name == path) {
+        return Some(index);
+    }
+    if !Path::new(path).is_file() {
+        return None;
+    }
+    match Profile::load(Path::new(path)) {
+        Ok(profile) => {
+            profiles.push((path.to_string(), profile));
+            Some(profiles.len() - 1)
+        }
+        Err(e) => {
+            println!("can't load {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Designates an agent: a built-in AI level, an external engine by its index in [`engines`],
+/// or a heuristic profile file by its index in [`PROFILES`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum AgentSpec {
+    Level(AILevel),
+    Engine(usize),
+    Profile(usize),
+}
+
+impl AgentSpec {
+    /// Returns all the agents: the available AI levels, the [`RATED_SKILLS`], then the external engines.
+    pub fn all() -> Vec<AgentSpec> {
+        AILevel::available()
+            .chain(RATED_SKILLS.map(AILevel::Skill))
+            .map(AgentSpec::Level)
+            .chain((0..engines().len()).map(AgentSpec::Engine))
+            .collect()
+    }
+
+    /// Returns the name of the agent, as read by [`AgentSpec::from_str`].
+    pub fn name(&self) -> String {
+        match self {
+            AgentSpec::Level(level) => level.name(),
+            AgentSpec::Engine(index) => engines()[*index].name.clone(),
+            AgentSpec::Profile(index) => PROFILES.lock().unwrap()[*index].0.clone(),
+        }
+    }
+
+    /// Converts the name of an AI level, of an external engine or the path of a heuristic profile
+    /// into an [`AgentSpec`].
+    /// Returns `None` if the input string does not match any agent.
+    pub fn from_str(s: &str) -> Option<Self> {
+        AILevel::from_str(s)
+            .map(AgentSpec::Level)
+            .or_else(|| {
+                engines()
+                    .iter()
+                    .position(|engine| engine.name == s)
+                    .map(AgentSpec::Engine)
+            })
+            .or_else(|| load_profile(s).map(AgentSpec::Profile))
+    }
+
+    /// Tells if the agent can play games that don't start from the empty board: CodinGame bots can't.
+    pub fn plays_openings(&self) -> bool {
+        match self {
+            AgentSpec::Engine(index) => engines()[*index].protocol != Protocol::CodinGame,
+            _ => true,
+        }
+    }
+
+    /// Creates the agent, launching the program of an external engine.
+    pub fn build(&self) -> Result<Box<dyn Agent>, AgentError> {
+        Ok(match self {
+            AgentSpec::Level(level) => Box::new(LevelAgent::new(*level)),
+            AgentSpec::Engine(index) => {
+                let config = &engines()[*index];
+                match config.protocol {
+                    Protocol::Engine => Box::new(ExternalEngine::launch(config)?),
+                    Protocol::CodinGame => Box::new(CodinGameBot::launch(config)?),
+                }
+            }
+            AgentSpec::Profile(index) => Box::new(ProfileAgent {
+                profile: PROFILES.lock().unwrap()[*index].1.clone(),
+                table: ScoreTable::new(),
+                evaluation: None,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agent_error_round_trip() {
+        for error in [
+            AgentError::Crashed("engine exit status: 1".to_string()),
+            AgentError::Timeout(Duration::from_millis(250)),
+            AgentError::IllegalMove("44".to_string()),
+            AgentError::Protocol("bestmove 9".to_string()),
+            AgentError::Protocol(String::new()),
+        ] {
+            assert_eq!(AgentError::from_str(&error.to_string()), Some(error));
+        }
+        assert_eq!(AgentError::from_str("lost"), None);
+        assert_eq!(AgentError::from_str("no move within a second ms"), None);
+    }
+}
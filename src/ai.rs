@@ -1,13 +1,270 @@
+use crate::model::model;
+use crate::morpion::is_won_by;
+use crate::random::with_rng;
+use crate::skill::{BOOK_SKILL, MAX_SKILL, MIN_SKILL, SOLVER_SKILL};
 use crate::{CellState, Morpion, Player, PlayingState};
-use rand::{self, Rng};
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
 const WEIGHTS_CENTER: [isize; 9] = [40, 10, 40, 10, 45, 10, 40, 10, 40];
 const WEIGHTS_CORNER: [isize; 9] = [45, 10, 45, 10, 15, 10, 45, 10, 45];
-const WINNING_WEIGHT: isize = 10000;
+/// The rows, columns and diagonals of a 3x3 grid.
+const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+    [0, 4, 8],
+    [2, 4, 6],
+];
+pub const WINNING_WEIGHT: isize = 10000;
+/// File from which the heuristic parameters are loaded when it exists.
+pub const PARAMS_FILE: &str = "heuristic_params.txt";
+
+static PARAMS: OnceLock<HeuristicParams> = OnceLock::new();
+
+/// A heuristic: scores a game state from the point of view of the maximizing player.
+pub type HeuristicFn<'a> = dyn Fn(&Morpion, Player) -> isize + 'a;
+
+/// Tunable parameters used by the heuristics.
+/// The default values are the hand-picked ones, see [`crate::tuning`] to optimise them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeuristicParams {
+    /// Positional weights of [`center_heuristic`].
+    pub weights_center: [isize; 9],
+    /// Positional weights of [`corner_heuristic`].
+    pub weights_corner: [isize; 9],
+    /// Multiplier applied to the weight of a won big cell in the weighted heuristics.
+    pub big_cell_factor: isize,
+    /// Multiplier applied to the winning sequences of the big board.
+    pub meta_sequence_factor: isize,
+    /// Bonus for any won big cell.
+    pub won_big_cell: isize,
+    /// Extra bonus for the won center big cell.
+    pub won_center_big_cell: isize,
+    /// Extra bonus for a won corner big cell.
+    pub won_corner_big_cell: isize,
+    /// Bonus for the center cell of a free big cell.
+    pub center_cell: isize,
+    /// Bonus for any cell of the free center big cell.
+    pub center_board_cell: isize,
+    /// Bonus given to the player who can play anywhere.
+    pub free_choice: isize,
+    /// Value of an open two-in-a-row in a free big cell, for [`threat_heuristic`].
+    pub local_threat: isize,
+    /// Value of an open two-in-a-row of won big cells.
+    pub meta_threat: isize,
+    /// Value of a won big cell, for [`threat_heuristic`].
+    pub threat_big_cell: isize,
+    /// Bonus for the player to move when they are sent to a big cell they can win right away.
+    pub sent_to_threat: isize,
+    /// Bonus for the player to move when they are sent to a finished big cell (free choice).
+    pub sent_to_free: isize,
+}
+
+impl Default for HeuristicParams {
+    fn default() -> Self {
+        Self {
+            weights_center: WEIGHTS_CENTER,
+            weights_corner: WEIGHTS_CORNER,
+            big_cell_factor: 50,
+            meta_sequence_factor: 2,
+            won_big_cell: 5,
+            won_center_big_cell: 10,
+            won_corner_big_cell: 3,
+            center_cell: 3,
+            center_board_cell: 3,
+            free_choice: 2,
+            local_threat: 3,
+            meta_threat: 30,
+            threat_big_cell: 20,
+            sent_to_threat: 15,
+            sent_to_free: 10,
+        }
+    }
+}
+
+impl HeuristicParams {
+    /// Names of the parameters, in the order used by [`HeuristicParams::values`].
+    pub const NAMES: [&'static str; 15] = [
+        "weights_center",
+        "weights_corner",
+        "big_cell_factor",
+        "meta_sequence_factor",
+        "won_big_cell",
+        "won_center_big_cell",
+        "won_corner_big_cell",
+        "center_cell",
+        "center_board_cell",
+        "free_choice",
+        "local_threat",
+        "meta_threat",
+        "threat_big_cell",
+        "sent_to_threat",
+        "sent_to_free",
+    ];
+
+    /// Returns the values of the parameter named `name`, or `None` if the name is unknown.
+    fn get(&self, name: &str) -> Option<&[isize]> {
+        Some(match name {
+            "weights_center" => &self.weights_center,
+            "weights_corner" => &self.weights_corner,
+            "big_cell_factor" => std::slice::from_ref(&self.big_cell_factor),
+            "meta_sequence_factor" => std::slice::from_ref(&self.meta_sequence_factor),
+            "won_big_cell" => std::slice::from_ref(&self.won_big_cell),
+            "won_center_big_cell" => std::slice::from_ref(&self.won_center_big_cell),
+            "won_corner_big_cell" => std::slice::from_ref(&self.won_corner_big_cell),
+            "center_cell" => std::slice::from_ref(&self.center_cell),
+            "center_board_cell" => std::slice::from_ref(&self.center_board_cell),
+            "free_choice" => std::slice::from_ref(&self.free_choice),
+            "local_threat" => std::slice::from_ref(&self.local_threat),
+            "meta_threat" => std::slice::from_ref(&self.meta_threat),
+            "threat_big_cell" => std::slice::from_ref(&self.threat_big_cell),
+            "sent_to_threat" => std::slice::from_ref(&self.sent_to_threat),
+            "sent_to_free" => std::slice::from_ref(&self.sent_to_free),
+            _ => return None,
+        })
+    }
+
+    /// Mutable version of [`HeuristicParams::get`].
+    fn get_mut(&mut self, name: &str) -> Option<&mut [isize]> {
+        Some(match name {
+            "weights_center" => &mut self.weights_center,
+            "weights_corner" => &mut self.weights_corner,
+            "big_cell_factor" => std::slice::from_mut(&mut self.big_cell_factor),
+            "meta_sequence_factor" => std::slice::from_mut(&mut self.meta_sequence_factor),
+            "won_big_cell" => std::slice::from_mut(&mut self.won_big_cell),
+            "won_center_big_cell" => std::slice::from_mut(&mut self.won_center_big_cell),
+            "won_corner_big_cell" => std::slice::from_mut(&mut self.won_corner_big_cell),
+            "center_cell" => std::slice::from_mut(&mut self.center_cell),
+            "center_board_cell" => std::slice::from_mut(&mut self.center_board_cell),
+            "free_choice" => std::slice::from_mut(&mut self.free_choice),
+            "local_threat" => std::slice::from_mut(&mut self.local_threat),
+            "meta_threat" => std::slice::from_mut(&mut self.meta_threat),
+            "threat_big_cell" => std::slice::from_mut(&mut self.threat_big_cell),
+            "sent_to_threat" => std::slice::from_mut(&mut self.sent_to_threat),
+            "sent_to_free" => std::slice::from_mut(&mut self.sent_to_free),
+            _ => return None,
+        })
+    }
+
+    /// Flattens all the parameters into a single vector (following [`HeuristicParams::NAMES`]).
+    pub fn values(&self) -> Vec<isize> {
+        Self::NAMES
+            .iter()
+            .flat_map(|name| self.get(name).unwrap().to_vec())
+            .collect()
+    }
+
+    /// Sets all the parameters from a vector produced by [`HeuristicParams::values`].
+    pub fn set_values(&mut self, values: &[isize]) {
+        let mut values = values.iter();
+        for name in Self::NAMES {
+            for value in self.get_mut(name).unwrap() {
+                *value = *values.next().unwrap();
+            }
+        }
+    }
+
+    /// Returns the range of indices (in [`HeuristicParams::values`]) of the parameter `name`.
+    pub fn range_of(&self, name: &str) -> std::ops::Range<usize> {
+        let mut start = 0;
+        for other in Self::NAMES {
+            let len = self.get(other).unwrap().len();
+            if other == name {
+                return start..start + len;
+            }
+            start += len;
+        }
+        panic!("unknown heuristic parameter: {}", name);
+    }
+
+    /// Parses parameters from their text representation (one `name = values...` line per parameter).
+    /// Missing parameters keep their default value and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut params = Self::default();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, values) = line
+                .split_once('=')
+                .ok_or_else(|| format!("invalid line: {}", line))?;
+            let name = name.trim();
+            let values = values
+                .split_whitespace()
+                .map(|v| v.parse::<isize>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("invalid value for {}: {}", name, e))?;
+            let slot = params
+                .get_mut(name)
+                .ok_or_else(|| format!("unknown parameter: {}", name))?;
+            if slot.len() != values.len() {
+                return Err(format!(
+                    "{} expects {} value(s), got {}",
+                    name,
+                    slot.len(),
+                    values.len()
+                ));
+            }
+            slot.copy_from_slice(&values);
+        }
+        Ok(params)
+    }
+
+    /// Loads parameters from a file written by [`HeuristicParams::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the parameters to a file, in the format read by [`HeuristicParams::load`].
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl std::fmt::Display for HeuristicParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for name in Self::NAMES {
+            let values: Vec<String> = self
+                .get(name)
+                .unwrap()
+                .iter()
+                .map(isize::to_string)
+                .collect();
+            writeln!(f, "{} = {}", name, values.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the parameters used by the heuristics.
+/// They are loaded from [`PARAMS_FILE`] the first time, falling back to the defaults.
+pub fn params() -> &'static HeuristicParams {
+    PARAMS.get_or_init(|| match HeuristicParams::load(Path::new(PARAMS_FILE)) {
+        Ok(params) => params,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => HeuristicParams::default(),
+        Err(e) => {
+            println!(
+                "can't load {}: {}, using default parameters",
+                PARAMS_FILE, e
+            );
+            HeuristicParams::default()
+        }
+    })
+}
 
 /// Represents the different AI difficulty levels.
 /// Determines the AI's decision-making complexity in the game.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum AILevel {
     /// The easiest difficulty, making basic and predictable moves.
     Easy,
@@ -15,28 +272,140 @@ pub enum AILevel {
     Medium,
     /// The hardest difficulty, utilizing advanced heuristics.
     Hard,
+    /// Looks at the threats and at the big cell each move sends the opponent to.
+    Threat,
+    /// Uses the heuristic and depth of the profile file (see [`crate::heuristic`]).
+    Custom,
+    /// Uses the evaluation model learned from self-play (see [`crate::model`]).
+    Learned,
+    /// Human-like AI, from 1 (beginner) to 20, making mistakes at low skills (see [`crate::skill`]).
+    Skill(u8),
 }
 
 impl AILevel {
+    /// All the fixed AI levels, in the order they are shown in the menu.
+    pub const ALL: [AILevel; 6] = [
+        AILevel::Easy,
+        AILevel::Medium,
+        AILevel::Hard,
+        AILevel::Threat,
+        AILevel::Custom,
+        AILevel::Learned,
+    ];
+
+    /// Tells if the level can play: the learned level needs a model (see [`crate::model::model`]).
+    pub fn is_available(&self) -> bool {
+        *self != AILevel::Learned || model().is_some()
+    }
+
+    /// Returns the fixed AI levels that can play, see [`AILevel::is_available`].
+    pub fn available() -> impl Iterator<Item = AILevel> {
+        AILevel::ALL.into_iter().filter(AILevel::is_available)
+    }
+
+    /// Returns what the level looks at before searching. The easy and medium levels only search,
+    /// the skill levels use the solver and the book from [`SOLVER_SKILL`] and [`BOOK_SKILL`].
+    pub fn aids(&self) -> Aids {
+        match self {
+            AILevel::Easy | AILevel::Medium => Aids {
+                solver: false,
+                book: false,
+            },
+            AILevel::Skill(skill) => Aids {
+                solver: *skill >= SOLVER_SKILL,
+                book: *skill >= BOOK_SKILL,
+            },
+            _ => Aids {
+                solver: true,
+                book: true,
+            },
+        }
+    }
+
+    /// Returns the name of the level, as read by [`AILevel::from_str`].
+    pub fn name(&self) -> String {
+        match self {
+            AILevel::Easy => "easy".to_string(),
+            AILevel::Medium => "medium".to_string(),
+            AILevel::Hard => "hard".to_string(),
+            AILevel::Threat => "threat".to_string(),
+            AILevel::Custom => "custom".to_string(),
+            AILevel::Learned => "learned".to_string(),
+            AILevel::Skill(skill) => format!("skill{}", skill),
+        }
+    }
+
     /// Converts a string representation of AI difficulty level into an [`AILevel`] enum.
-    /// Returns `None` if the input string does not match any known level.
+    /// Returns `None` if the input string does not match any known level, or if the level is not available.
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "easy" => Some(AILevel::Easy),
             "medium" => Some(AILevel::Medium),
             "hard" => Some(AILevel::Hard),
-            _ => None,
+            "threat" => Some(AILevel::Threat),
+            "custom" => Some(AILevel::Custom),
+            "learned" => Some(AILevel::Learned).filter(AILevel::is_available),
+            _ => s
+                .strip_prefix("skill")
+                .and_then(|skill| skill.parse::<u8>().ok())
+                .filter(|skill| (MIN_SKILL..=MAX_SKILL).contains(skill))
+                .map(AILevel::Skill),
+        }
+    }
+}
+
+/// What an AI looks at before searching its move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aids {
+    /// Plays the exact move of the solver near the end of the game (see [`crate::solver`]).
+    pub solver: bool,
+    /// Plays the moves of the opening book (see [`crate::book`]).
+    pub book: bool,
+}
+
+/// Evaluation of a position, as shown to the players.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Evaluation {
+    /// Score of the best move found by the search, from `X`'s point of view.
+    Score(isize),
+    /// Result proven by the solver, with the number of moves before the win.
+    Proven(PlayingState, usize),
+}
+
+impl Evaluation {
+    /// Returns the expected share of the game for `X`, between `0.0` (`O` wins) and `1.0` (`X` wins).
+    pub fn x_share(&self) -> f32 {
+        match self {
+            Evaluation::Score(score) => 1.0 / (1.0 + (-(*score as f32) / 500.0).exp()),
+            Evaluation::Proven(PlayingState::Win(Player::X), _) => 1.0,
+            Evaluation::Proven(PlayingState::Win(Player::O), _) => 0.0,
+            Evaluation::Proven(_, _) => 0.5,
+        }
+    }
+}
+
+impl std::fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Evaluation::Score(score) => write!(f, "{:+} for X", score),
+            Evaluation::Proven(PlayingState::Win(player), 0) => write!(f, "{} has won", player),
+            Evaluation::Proven(PlayingState::Win(player), distance) => {
+                write!(f, "forced win for {} in {}", player, distance)
+            }
+            Evaluation::Proven(_, _) => write!(f, "forced tie"),
         }
     }
 }
 
 /// Implements the _Minimax algorithm_ for decision-making in the game.
 /// Evaluates possible moves and returns the best score for the maximizing player.
+/// Only used to check [`alpha_beta`], which finds the same scores faster.
+#[cfg(test)]
 pub fn minimax(
     node: &Morpion,
     depth: isize,
     maximizing_player: Player,
-    heuristic: &dyn Fn(&Morpion, Player) -> isize,
+    heuristic: &HeuristicFn,
 ) -> isize {
     if node.state != PlayingState::Continue || depth == 0 {
         return heuristic(node, maximizing_player);
@@ -55,21 +424,77 @@ pub fn minimax(
     value
 }
 
+/// Maximum number of entries of a [`ScoreTable`].
+const SCORE_TABLE_CAPACITY: usize = 1 << 18;
+
+/// Bound stored in a [`ScoreTable`], since alpha-beta scores are not always exact.
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// Scores found by [`alpha_beta`] by position, remaining depth and side of the maximizing player,
+/// so that the searches of an AI share their work (e.g. pondering and the search that follows).
+/// The scores depend on the heuristic: a table must only be used with one heuristic. Cleared when full.
+pub struct ScoreTable {
+    entries: HashMap<(u64, isize, bool), (isize, Bound)>,
+}
+
+impl ScoreTable {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    fn insert(&mut self, key: (u64, isize, bool), entry: (isize, Bound)) {
+        if self.entries.len() >= SCORE_TABLE_CAPACITY {
+            self.entries.clear();
+        }
+        self.entries.insert(key, entry);
+    }
+}
+
 /// Implements the _Alpha-Beta Pruning optimization_ for the _Minimax algorithm_.
 /// Reduces the number of nodes evaluated by pruning branches that won't be selected.
+/// The scores of the inner nodes are kept in `table`, a score found again is not searched twice.
+/// The search is aborted as soon as `stop` is set, the returned score is then meaningless.
+#[allow(clippy::too_many_arguments)]
 pub fn alpha_beta(
     node: &Morpion,
     depth: isize,
     mut alpha: isize,
     mut beta: isize,
     maximizing_player: Player,
-    heuristic: fn(&Morpion, Player) -> isize,
+    heuristic: &HeuristicFn,
+    table: &mut ScoreTable,
+    stop: &AtomicBool,
 ) -> isize {
+    if stop.load(Ordering::Relaxed) {
+        return 0;
+    }
     if node.state != PlayingState::Continue || depth == 0 {
         return heuristic(node, maximizing_player) * (depth + 1);
     }
-    if node.player == maximizing_player {
-        let mut value = isize::MIN;
+    let maximizing = node.player == maximizing_player;
+    let key = (node.hash(), depth, maximizing);
+    // Scores outside of the window are bounds, those inside are exact
+    if let Some(&(score, bound)) = table.entries.get(&key) {
+        match bound {
+            Bound::Exact => return score,
+            Bound::Lower => alpha = alpha.max(score),
+            Bound::Upper => beta = beta.min(score),
+        }
+        if alpha > beta {
+            return score;
+        }
+    }
+    let (window_alpha, window_beta) = (alpha, beta);
+    let mut value;
+    if maximizing {
+        value = isize::MIN;
         for child in generate_children(node) {
             value = value.max(alpha_beta(
                 &child,
@@ -78,35 +503,49 @@ pub fn alpha_beta(
                 beta,
                 maximizing_player,
                 heuristic,
+                table,
+                stop,
             ));
             if value > beta {
                 break;
             }
             alpha = alpha.max(value);
         }
-        return value;
-    }
-    let mut value = isize::MAX;
-    for child in generate_children(node) {
-        value = value.min(alpha_beta(
-            &child,
-            depth - 1,
-            alpha,
-            beta,
-            maximizing_player,
-            heuristic,
-        ));
-        if value < alpha {
-            break;
+    } else {
+        value = isize::MAX;
+        for child in generate_children(node) {
+            value = value.min(alpha_beta(
+                &child,
+                depth - 1,
+                alpha,
+                beta,
+                maximizing_player,
+                heuristic,
+                table,
+                stop,
+            ));
+            if value < alpha {
+                break;
+            }
+            beta = beta.min(value);
         }
-        beta = beta.min(value);
+    }
+    if !stop.load(Ordering::Relaxed) {
+        let bound = if value < window_alpha {
+            Bound::Upper
+        } else if value > window_beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        table.insert(key, (value, bound));
     }
     value
 }
 
 /// Determines the direction of evaluation for a given player.
 /// Returns `1` if the actual player is the maximizing player, otherwise `-1`.
-fn dir(actual_player: Player, maximizing_player: Player) -> isize {
+pub fn dir(actual_player: Player, maximizing_player: Player) -> isize {
     if actual_player == maximizing_player {
         1
     } else {
@@ -116,22 +555,29 @@ fn dir(actual_player: Player, maximizing_player: Player) -> isize {
 
 /// Evaluates a game state using a weighted heuristic based on predefined weights.
 /// Weights influence the importance of different positions on the board.
-fn weighted_heuristic(node: &Morpion, maximizing_player: Player, weights: [isize; 9]) -> isize {
+pub fn weighted_heuristic(
+    node: &Morpion,
+    maximizing_player: Player,
+    weights: &[isize; 9],
+    big_cell_factor: isize,
+) -> isize {
     let mut score: isize = 0;
     match node.state {
         PlayingState::Continue => {
             for big_cell_index in 0..9 {
                 match node.board.states[big_cell_index] {
                     CellState::Occupied(player) => {
-                        score += dir(player, maximizing_player) * 50 * weights[big_cell_index]
+                        score += dir(player, maximizing_player)
+                            * big_cell_factor
+                            * weights[big_cell_index]
                     }
                     CellState::Tie => {}
                     CellState::Free => {
-                        for lil_cell_index in 0..9 {
-                            if let CellState::Occupied(player) =
-                                node.board.cells[big_cell_index][lil_cell_index]
-                            {
-                                score += dir(player, maximizing_player) * weights[lil_cell_index];
+                        for (lil_cell, weight) in
+                            node.board.cells[big_cell_index].iter().zip(weights)
+                        {
+                            if let CellState::Occupied(player) = lil_cell {
+                                score += dir(*player, maximizing_player) * weight;
                             }
                         }
                     }
@@ -148,35 +594,73 @@ fn weighted_heuristic(node: &Morpion, maximizing_player: Player, weights: [isize
 /// Heuristic function that prioritizes the center of the board.
 /// Returns a score based on weighted positions with a preference for central control.
 pub fn center_heuristic(node: &Morpion, maximizing_player: Player) -> isize {
-    weighted_heuristic(node, maximizing_player, WEIGHTS_CENTER)
+    center_heuristic_with(node, maximizing_player, params())
+}
+
+/// [`center_heuristic`] using the given parameters instead of the loaded ones.
+pub fn center_heuristic_with(
+    node: &Morpion,
+    maximizing_player: Player,
+    params: &HeuristicParams,
+) -> isize {
+    weighted_heuristic(
+        node,
+        maximizing_player,
+        &params.weights_center,
+        params.big_cell_factor,
+    )
 }
 
 /// Heuristic function that prioritizes the corners of the board.
 /// Returns a score based on weighted positions, favoring corner control.
 pub fn corner_heuristic(node: &Morpion, maximizing_player: Player) -> isize {
-    weighted_heuristic(node, maximizing_player, WEIGHTS_CORNER)
+    corner_heuristic_with(node, maximizing_player, params())
+}
+
+/// [`corner_heuristic`] using the given parameters instead of the loaded ones.
+pub fn corner_heuristic_with(
+    node: &Morpion,
+    maximizing_player: Player,
+    params: &HeuristicParams,
+) -> isize {
+    weighted_heuristic(
+        node,
+        maximizing_player,
+        &params.weights_corner,
+        params.big_cell_factor,
+    )
 }
 
 /// Evaluates the game state based on winning sequences.
 /// Considers aligned marks that may lead to a win and assigns scores accordingly.
 pub fn winning_sequence_heuristic(node: &Morpion, maximizing_player: Player) -> isize {
+    winning_sequence_heuristic_with(node, maximizing_player, params())
+}
+
+/// [`winning_sequence_heuristic`] using the given parameters instead of the loaded ones.
+pub fn winning_sequence_heuristic_with(
+    node: &Morpion,
+    maximizing_player: Player,
+    params: &HeuristicParams,
+) -> isize {
     let mut score: isize = 0;
     match node.state {
         PlayingState::Continue => {
-            score += evaluate_winning_sequence(&node.board.states, maximizing_player) * 2;
+            score += evaluate_winning_sequence(&node.board.states, maximizing_player)
+                * params.meta_sequence_factor;
             for big_cell_index in 0..9 {
                 match node.board.states[big_cell_index] {
                     CellState::Occupied(player) => {
                         let dir = dir(player, maximizing_player);
-                        score += dir * 5;
+                        score += dir * params.won_big_cell;
                         if big_cell_index == 4 {
-                            score += dir * 10;
+                            score += dir * params.won_center_big_cell;
                         } else if big_cell_index == 0
                             || big_cell_index == 2
                             || big_cell_index == 6
                             || big_cell_index == 8
                         {
-                            score += dir * 3;
+                            score += dir * params.won_corner_big_cell;
                         }
                     }
                     CellState::Free => {
@@ -190,10 +674,10 @@ pub fn winning_sequence_heuristic(node: &Morpion, maximizing_player: Player) ->
                             {
                                 let dir = dir(player, maximizing_player);
                                 if lil_cell_index == 4 {
-                                    score += dir * 3;
+                                    score += dir * params.center_cell;
                                 }
                                 if big_cell_index == 4 {
-                                    score += dir * 3;
+                                    score += dir * params.center_board_cell;
                                 }
                             }
                         }
@@ -214,12 +698,107 @@ pub fn winning_sequence_heuristic(node: &Morpion, maximizing_player: Player) ->
 pub fn everywhere_heuristic(node: &Morpion, maximizing_player: Player) -> isize {
     let mut score: isize = winning_sequence_heuristic(node, maximizing_player);
     if node.focused_big_cell.is_none() {
-        score += dir(node.player, maximizing_player) * 2
+        score += dir(node.player, maximizing_player) * params().free_choice
     }
 
     score
 }
 
+/// [`everywhere_heuristic`] using the given parameters instead of the loaded ones.
+pub fn everywhere_heuristic_with(
+    node: &Morpion,
+    maximizing_player: Player,
+    params: &HeuristicParams,
+) -> isize {
+    let mut score: isize = winning_sequence_heuristic_with(node, maximizing_player, params);
+    if node.focused_big_cell.is_none() {
+        score += dir(node.player, maximizing_player) * params.free_choice
+    }
+
+    score
+}
+
+/// Heuristic based on the threats of both players and on where the last move sent the opponent.
+/// Scores open two-in-a-rows in the big cells and on the big board, and penalizes sending
+/// the opponent to a big cell they can win or giving them the free choice.
+pub fn threat_heuristic(node: &Morpion, maximizing_player: Player) -> isize {
+    threat_heuristic_with(node, maximizing_player, params())
+}
+
+/// [`threat_heuristic`] using the given parameters instead of the loaded ones.
+pub fn threat_heuristic_with(
+    node: &Morpion,
+    maximizing_player: Player,
+    params: &HeuristicParams,
+) -> isize {
+    let mut score: isize = 0;
+    match node.state {
+        PlayingState::Continue => {
+            let opponent = maximizing_player.other();
+            let states = &node.board.states;
+            score += (count_threats(states, maximizing_player) - count_threats(states, opponent))
+                * params.meta_threat;
+            for (state, cells) in states.iter().zip(node.board.cells.iter()) {
+                match state {
+                    CellState::Occupied(player) => {
+                        score += dir(*player, maximizing_player) * params.threat_big_cell
+                    }
+                    CellState::Free => {
+                        score += (count_threats(cells, maximizing_player)
+                            - count_threats(cells, opponent))
+                            * params.local_threat;
+                    }
+                    CellState::Tie => {}
+                }
+            }
+            // The player to move was sent there by the previous move
+            let dir = dir(node.player, maximizing_player);
+            match node.focused_big_cell {
+                None => score += dir * params.sent_to_free,
+                Some(big_cell_index) => {
+                    if count_threats(&node.board.cells[big_cell_index], node.player) > 0 {
+                        score += dir * params.sent_to_threat;
+                        if completes_line(states, big_cell_index, node.player) {
+                            score += dir * params.meta_threat;
+                        }
+                    }
+                }
+            }
+        }
+        PlayingState::Win(player) => score += dir(player, maximizing_player) * WINNING_WEIGHT,
+        PlayingState::Tie => {}
+    }
+
+    score
+}
+
+/// Counts the lines of a 3x3 grid where `player` has two marks and the third cell is free.
+pub fn count_threats(states: &[CellState; 9], player: Player) -> isize {
+    LINES
+        .iter()
+        .filter(|line| {
+            let owned = line
+                .iter()
+                .filter(|&&i| states[i] == CellState::Occupied(player))
+                .count();
+            let free = line
+                .iter()
+                .filter(|&&i| states[i] == CellState::Free)
+                .count();
+            owned == 2 && free == 1
+        })
+        .count() as isize
+}
+
+/// Tells if taking the free cell `index` of a 3x3 grid would complete a line for `player`.
+fn completes_line(states: &[CellState; 9], index: usize, player: Player) -> bool {
+    states[index] == CellState::Free && {
+        let mut states = *states;
+        states[index] = CellState::Occupied(player);
+        is_won_by(&states, player)
+    }
+}
+
 /// Analyzes the board to find _winning sequences_.
 /// A winning sequence is defined as two aligned marks in a row, column, or diagonal.
 /// Returns a cumulative score for detected sequences.
@@ -272,25 +851,110 @@ pub fn evaluate_winning_sequence(states: &[CellState; 9], maximizing_player: Pla
     score
 }
 
-/// Generates all possible game states from the current node by simulating valid moves.
-/// Returns a vector of new game states representing all potential child nodes.
-pub fn generate_children(node: &Morpion) -> Vec<Morpion> {
-    let mut children = Vec::new();
+/// Generates all the valid moves `(ult_index, index)` from the current node.
+pub fn generate_moves(node: &Morpion) -> Vec<(usize, usize)> {
+    let mut moves = Vec::new();
     for i in 0..9 {
         for j in 0..9 {
             if node.index_is_playable(i, j) {
-                let mut new_node = node.clone();
-                new_node.play_at(i, j);
-                children.push(new_node);
+                moves.push((i, j));
             }
         }
     }
-    children
+    moves
+}
+
+/// Generates all possible game states from the current node by simulating valid moves.
+/// Returns a vector of new game states representing all potential child nodes.
+pub fn generate_children(node: &Morpion) -> Vec<Morpion> {
+    generate_moves(node)
+        .into_iter()
+        .map(|(i, j)| {
+            let mut new_node = node.clone();
+            new_node.play_at(i, j);
+            new_node
+        })
+        .collect()
 }
 
 /// Generates a random noise value within the specified range.
 /// Can be used to introduce randomness in AI decision-making.
 pub fn noise(range: i32) -> isize {
-    let mut rng = rand::rng();
-    rng.random_range(-range..range) as isize
+    with_rng(|rng| rng.random_range(-range..range) as isize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alpha_beta_scores_as_minimax() {
+        let stop = AtomicBool::new(false);
+        let mut node = Morpion::new();
+        for ply in 0..6 {
+            let player = node.player;
+            assert_eq!(
+                alpha_beta(
+                    &node,
+                    3,
+                    isize::MIN,
+                    isize::MAX,
+                    player,
+                    &everywhere_heuristic,
+                    &mut ScoreTable::new(),
+                    &stop
+                ),
+                minimax(&node, 3, player, &everywhere_heuristic)
+            );
+            let moves = generate_moves(&node);
+            let (ult_index, index) = moves[ply * 7 % moves.len()];
+            node.play_at(ult_index, index);
+        }
+    }
+
+    #[test]
+    fn score_table_keeps_exact_scores() {
+        let stop = AtomicBool::new(false);
+        let mut table = ScoreTable::new();
+        let mut node = Morpion::new();
+        for ply in 0..6 {
+            for depth in [2, 3] {
+                assert_eq!(
+                    node.score_moves_with(depth, &everywhere_heuristic, &mut table, &stop),
+                    node.score_moves_with(
+                        depth,
+                        &everywhere_heuristic,
+                        &mut ScoreTable::new(),
+                        &stop
+                    )
+                );
+            }
+            let moves = generate_moves(&node);
+            let (ult_index, index) = moves[ply * 5 % moves.len()];
+            node.play_at(ult_index, index);
+        }
+    }
+
+    #[test]
+    fn threats_of_a_grid() {
+        use CellState::{Free, Occupied};
+        let (x, o) = (Occupied(Player::X), Occupied(Player::O));
+        let states = [x, x, Free, o, Free, o, x, o, o];
+        assert_eq!(count_threats(&states, Player::X), 1);
+        assert_eq!(count_threats(&states, Player::O), 2);
+    }
+
+    #[test]
+    fn threat_heuristic_of_a_position() {
+        // X and O each have a threat in a big cell, O is sent to the big cell of its threat
+        let node = Morpion::from_notation(
+            "xx......./o.o....../........./........./........./........./........./........./........x o 1",
+        )
+        .unwrap();
+        let params = HeuristicParams::default();
+        // X's threat, O's threat, and O sent where it can win a big cell
+        let expected = params.local_threat - params.local_threat - params.sent_to_threat;
+        assert_eq!(threat_heuristic_with(&node, Player::X, &params), expected);
+        assert_eq!(threat_heuristic_with(&node, Player::O, &params), -expected);
+    }
 }
@@ -0,0 +1,25 @@
+use std::sync::atomic::AtomicBool;
+
+use crate::ai::{AILevel, ScoreTable};
+use crate::morpion::{format_move, Morpion};
+
+/// Analyses the position given in notation (see [`Morpion::from_notation`]) with the search of an AI level,
+/// and prints its evaluation and the score of every move for the player to move, best first.
+pub fn launch_analysis(notation: &str, ai_level: AILevel) -> Result<(), String> {
+    let morpion =
+        Morpion::from_notation(notation).map_err(|e| format!("invalid position: {}", e))?;
+    println!("{}\n{}", morpion, morpion.to_notation());
+    let never = AtomicBool::new(false);
+    if let Some(evaluation) = morpion.evaluate(ai_level, &never) {
+        println!("=> evaluation ({}): {}", ai_level.name(), evaluation);
+    }
+    if morpion.is_over() {
+        return Ok(());
+    }
+    let mut scores = morpion.score_moves(ai_level, &mut ScoreTable::new(), &never);
+    scores.sort_by_key(|(_, score)| -score);
+    for (m, score) in scores {
+        println!("=> {}: {:+}", format_move(m), score);
+    }
+    Ok(())
+}
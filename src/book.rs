@@ -0,0 +1,214 @@
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::OnceLock;
+
+use crate::ai::{AILevel, ScoreTable};
+use crate::morpion::{format_move, parse_move, Morpion};
+use crate::random::with_rng;
+
+/// File from which the opening book used by the AI is loaded when it exists.
+pub const BOOK_FILE: &str = "book.txt";
+/// The AI only consults the book during the first plies of the game.
+pub const BOOK_MAX_PLIES: usize = 8;
+
+static BOOK: OnceLock<Book> = OnceLock::new();
+
+/// A move `(ult_index, index)` of the book with its weight.
+pub type WeightedMove = ((usize, usize), u32);
+
+/// An opening book: for each known position (by [`Morpion::hash`]), the moves to play with their weights.
+#[derive(Default)]
+pub struct Book {
+    entries: HashMap<u64, Vec<WeightedMove>>,
+}
+
+impl Book {
+    /// Returns the weighted moves known for the position, if any.
+    pub fn moves(&self, morpion: &Morpion) -> Option<&[WeightedMove]> {
+        self.entries.get(&morpion.hash()).map(Vec::as_slice)
+    }
+
+    /// Picks one of the moves known for the position, randomly according to their weights.
+    /// The moves that can't be played, from an outdated book or a hash collision, are left out.
+    pub fn pick(&self, morpion: &Morpion) -> Option<(usize, usize)> {
+        let moves: Vec<&WeightedMove> = self
+            .moves(morpion)?
+            .iter()
+            .filter(|((ult_index, index), _)| morpion.index_is_playable(*ult_index, *index))
+            .collect();
+        let total: u32 = moves.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut choice = with_rng(|rng| rng.random_range(0..total));
+        for (book_move, weight) in moves {
+            if choice < *weight {
+                return Some(*book_move);
+            }
+            choice -= weight;
+        }
+        None
+    }
+
+    /// Adds `weight` to a move of the position.
+    pub fn add(&mut self, morpion: &Morpion, book_move: (usize, usize), weight: u32) {
+        let moves = self.entries.entry(morpion.hash()).or_default();
+        match moves.iter_mut().find(|(m, _)| *m == book_move) {
+            Some((_, w)) => *w += weight,
+            None => moves.push((book_move, weight)),
+        }
+    }
+
+    /// Parses a book from its text representation:
+    /// one line per position, with the hash in hexadecimal followed by `move:weight` pairs.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut book = Book::default();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let hash = fields.next().unwrap();
+            let hash = u64::from_str_radix(hash, 16)
+                .map_err(|e| format!("invalid position hash {}: {}", hash, e))?;
+            let moves = fields
+                .map(|field| {
+                    let (book_move, weight) = field
+                        .split_once(':')
+                        .ok_or_else(|| format!("invalid book move: {}", field))?;
+                    let weight = weight
+                        .parse::<u32>()
+                        .map_err(|e| format!("invalid weight {}: {}", weight, e))?;
+                    Ok((parse_move(book_move)?, weight))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            book.entries.insert(hash, moves);
+        }
+        Ok(book)
+    }
+
+    /// Loads a book from a file written by [`Book::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the book to a file, in the format read by [`Book::load`].
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut hashes: Vec<&u64> = self.entries.keys().collect();
+        hashes.sort();
+        let mut text = String::new();
+        for hash in hashes {
+            let moves: Vec<String> = self.entries[hash]
+                .iter()
+                .map(|(book_move, weight)| format!("{}:{}", format_move(*book_move), weight))
+                .collect();
+            text += &format!("{:016x} {}\n", hash, moves.join(" "));
+        }
+        fs::write(path, text)
+    }
+
+    /// Returns the number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Returns the opening book used by the AI.
+/// It is loaded from [`BOOK_FILE`] the first time, falling back to an empty book.
+pub fn book() -> &'static Book {
+    BOOK.get_or_init(|| match Book::load(Path::new(BOOK_FILE)) {
+        Ok(book) => book,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Book::default(),
+        Err(e) => {
+            println!(
+                "can't load {}: {}, playing without opening book",
+                BOOK_FILE, e
+            );
+            Book::default()
+        }
+    })
+}
+
+/// Returns a move from the opening book if the game is still in its first plies.
+pub fn book_move(morpion: &Morpion) -> Option<(usize, usize)> {
+    if morpion.ply() >= BOOK_MAX_PLIES {
+        return None;
+    }
+    book().pick(morpion)
+}
+
+/// Builds an opening book from searches of the given level.
+/// From each position, the `width` best moves are kept (the best one gets the biggest weight)
+/// and explored in turn, up to `plies` moves from the start.
+pub fn generate_book(plies: usize, width: usize, level: AILevel) -> Book {
+    let mut book = Book::default();
+    let mut frontier = vec![Morpion::new()];
+    let mut table = ScoreTable::new();
+    for ply in 0..plies {
+        let mut next_frontier = Vec::new();
+        for (position_index, morpion) in frontier.iter().enumerate() {
+            if morpion.is_over() || book.moves(morpion).is_some() {
+                continue;
+            }
+            let mut scores = morpion.score_moves(level, &mut table, &AtomicBool::new(false));
+            scores.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+            for (rank, (book_move, _)) in scores.into_iter().take(width).enumerate() {
+                book.add(morpion, book_move, (width - rank) as u32);
+                let mut child = morpion.clone();
+                child.play_at(book_move.0, book_move.1);
+                next_frontier.push(child);
+            }
+            println!(
+                "ply {}: position {}/{} searched",
+                ply + 1,
+                position_index + 1,
+                frontier.len()
+            );
+        }
+        frontier = next_frontier;
+    }
+    book
+}
+
+/// Generates an opening book with searches of the given level and writes it to `output`.
+pub fn launch_book_generation(
+    plies: usize,
+    width: usize,
+    level: AILevel,
+    output: &Path,
+) -> Result<(), String> {
+    let book = generate_book(plies, width, level);
+    book.save(output)
+        .map_err(|e| format!("can't write {}: {}", output.display(), e))?;
+    println!(
+        "-- opening book with {} positions written to {} --",
+        book.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_leaves_out_unplayable_moves() {
+        let mut morpion = Morpion::new();
+        let mut book = Book::default();
+        morpion.play_at(4, 4);
+        // Outside the focused big cell, then on the occupied cell
+        book.add(&morpion, (0, 0), 5);
+        book.add(&morpion, (4, 4), 5);
+        assert_eq!(book.pick(&morpion), None);
+        book.add(&morpion, (4, 0), 1);
+        for _ in 0..10 {
+            assert_eq!(book.pick(&morpion), Some((4, 0)));
+        }
+    }
+}
@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::agent::{engines, AgentSpec, ENGINES_FILE};
+use crate::ai::AILevel;
+use crate::analysis::launch_analysis;
+use crate::book::launch_book_generation;
+use crate::codingame::launch_codingame;
+use crate::engine::launch_engine;
+use crate::fight::{
+    default_threads, launch_fights, resume_fights, FightOptions, Openings, TimeControl,
+};
+use crate::gamestats::launch_game_stats;
+use crate::model::{launch_training, MODEL_FILE};
+use crate::morpion::Morpion;
+use crate::perft::launch_perft;
+use crate::rating::{launch_rating, RATINGS_FILE};
+use crate::replay::Replay;
+use crate::report::Format;
+use crate::skill::{MAX_SKILL, MIN_SKILL};
+use crate::solver::launch_solve;
+use crate::stats::{launch_sprt, Sprt};
+use crate::tournament::{launch_tournament, Schedule};
+use crate::tuning::{launch_tuning, Tunable};
+
+/// Name of the program in the usage.
+const PROGRAM: &str = "ultimate-morpion";
+/// Games played by `fight` when their number is not given.
+const DEFAULT_FIGHTS: usize = 50;
+/// Most games played by `sprt` when no hypothesis is accepted, if not given.
+const DEFAULT_SPRT_GAMES: usize = 10000;
+
+/// A command of the command line.
+struct Command {
+    name: &'static str,
+    /// Positional arguments as shown in the usage, the optional ones in brackets.
+    arguments: &'static str,
+    about: &'static str,
+    /// Options with the name of their value (empty for a flag) and what they do.
+    options: &'static [(&'static str, &'static str, &'static str)],
+}
+
+/// Options of the commands that play games on several threads.
+const THREADS: (&str, &str, &str) = (
+    "--threads",
+    "<n>",
+    "threads playing games (default: one per CPU core)",
+);
+
+const COMMANDS: [Command; 16] = [
+    Command {
+        name: "play",
+        arguments: "",
+        about: "Open the game window (the default command)",
+        options: &[],
+    },
+    Command {
+        name: "fight",
+        arguments: "<x> <o> [games]",
+        about: "Play games between two agents (50 by default) and print the results",
+        options: &[
+            THREADS,
+            ("--seed", "<n>", "seed of the first game, the next ones using the following seeds"),
+            ("--openings", "<file>", "start from the openings of a suite, each one played twice with swapped sides"),
+            ("--random-openings", "<plies>", "start from openings of random moves, played twice with swapped sides"),
+            ("--time", "<ms|base+inc>", "time per move, or time per game plus increment per move, in milliseconds (external programs only)"),
+            ("--format", "<json|csv>", "write a machine-readable report"),
+            ("--output", "<file>", "file of the report (default: stdout, without the text output)"),
+            ("--records", "<dir>", "save every game as a game record in a directory"),
+            ("--session", "<file>", "save the games as they finish in a session file"),
+            ("--resume", "<file>", "finish an interrupted session, instead of giving the agents"),
+        ],
+    },
+    Command {
+        name: "analyze",
+        arguments: "<position>",
+        about: "Evaluate a position and score its moves",
+        options: &[("--level", "<level>", "AI level of the search (default: hard)")],
+    },
+    Command {
+        name: "solve",
+        arguments: "<position>",
+        about: "Find the exact result of a position and its best move",
+        options: &[],
+    },
+    Command {
+        name: "perft",
+        arguments: "<depth> [position]",
+        about: "Count the positions reached by every sequence of moves, up to a depth",
+        options: &[],
+    },
+    Command {
+        name: "engine",
+        arguments: "",
+        about: "Play through the engine protocol on stdin and stdout",
+        options: &[],
+    },
+    Command {
+        name: "replay",
+        arguments: "<record>",
+        about: "Replay a game record in the game window",
+        options: &[
+            ("--game", "<n>", "replay the game n of a fight session file instead"),
+            ("--text", "", "print the positions of the game instead of opening the window"),
+        ],
+    },
+    Command {
+        name: "stats",
+        arguments: "<session|dir>",
+        about: "Print statistics on the games of a fight session file or of a directory of game records",
+        options: &[],
+    },
+    Command {
+        name: "tournament",
+        arguments: "<round-robin|gauntlet> <games> <output> <agents...>",
+        about: "Play a tournament between agents (the first one against the others in a gauntlet)",
+        options: &[THREADS],
+    },
+    Command {
+        name: "sprt",
+        arguments: "<a> <b> <elo0> <elo1>",
+        about: "Test whether a is at most elo0 (H0) or at least elo1 (H1) Elo stronger than b",
+        options: &[
+            THREADS,
+            ("--max-games", "<n>", "games played at most if no hypothesis is accepted (default: 10000)"),
+        ],
+    },
+    Command {
+        name: "rate",
+        arguments: "<games>",
+        about: "Rate the AI levels, a few skills and the external engines with a round-robin",
+        options: &[THREADS],
+    },
+    Command {
+        name: "tune",
+        arguments: "<center|corner|everywhere|threat> <games> <output>",
+        about: "Tune the parameters of a heuristic on self-play games of the level using it",
+        options: &[
+            ("--records", "<session|dir>", "also tune on the games of a fight session file or of a directory of game records"),
+        ],
+    },
+    Command {
+        name: "train",
+        arguments: "<hidden-units> <games> <output>",
+        about: "Train the evaluation model on self-play games",
+        options: &[],
+    },
+    Command {
+        name: "book",
+        arguments: "<plies> <width> <output>",
+        about: "Generate an opening book with searches of the hard level",
+        options: &[],
+    },
+    Command {
+        name: "codingame",
+        arguments: "[agent]",
+        about: "Play through the CodinGame protocol on stdin and stdout",
+        options: &[],
+    },
+    Command {
+        name: "help",
+        arguments: "[command]",
+        about: "Print the help of the program or of a command",
+        options: &[],
+    },
+];
+
+/// Error of the command line.
+#[derive(Debug)]
+pub enum CliError {
+    /// The command line is wrong, the message tells how to fix it.
+    Usage(String),
+    /// The command failed.
+    Failure(String),
+}
+
+/// What is left to do once the command is run.
+pub enum Launch {
+    Done,
+    /// Opening the game window, on the start menu or on a replayed game.
+    Window(Option<Box<Replay>>),
+}
+
+/// Returns the help of the program: the commands, the agents and the positions.
+fn help() -> String {
+    let mut help = format!(
+        "Usage: {} [command] [arguments] [options]\n\nCommands:\n",
+        PROGRAM
+    );
+    for command in &COMMANDS {
+        help += &format!("  {:<11} {}\n", command.name, command.about);
+    }
+    help += &format!(
+        "\n{}\n{}\n\nRun `{} help <command>` for the arguments and options of a command.",
+        agents_help(),
+        POSITION_HELP,
+        PROGRAM
+    );
+    help
+}
+
+/// Returns the help of a command: its usage and its options.
+fn command_help(command: &Command) -> String {
+    let mut help = format!(
+        "Usage: {} {} {}{}\n\n{}\n",
+        PROGRAM,
+        command.name,
+        command.arguments,
+        if command.options.is_empty() {
+            ""
+        } else {
+            " [options]"
+        },
+        command.about
+    );
+    if !command.options.is_empty() {
+        help += "\nOptions:\n";
+        for (name, value, about) in command.options {
+            help += &format!("  {:<30} {}\n", format!("{} {}", name, value), about);
+        }
+    }
+    help
+}
+
+/// Tells how positions are written.
+const POSITION_HELP: &str = "Positions are written in notation, between quotes: the 9 local boards from the top left \
+as 9 cells of x, o or . separated by /, then the player to move (x or o) and the board to play in (0 to 8, - for any).";
+
+/// Lists the valid agents: the AI levels, the external engines and the heuristic profiles.
+fn agents_help() -> String {
+    let names: Vec<&str> = engines()
+        .iter()
+        .map(|engine| engine.name.as_str())
+        .collect();
+    format!(
+        "{}\nExternal engines ({}): {}\nAgents can also be the path of a heuristic profile file.",
+        levels_help(),
+        ENGINES_FILE,
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(", ")
+        }
+    )
+}
+
+/// Lists the valid AI levels, telling how to make the learned level available if it is not.
+fn levels_help() -> String {
+    let levels: Vec<String> = AILevel::available().map(|level| level.name()).collect();
+    let learned = if AILevel::Learned.is_available() {
+        String::new()
+    } else {
+        format!(
+            " (learned needs a model in {}, see `{} help train`)",
+            MODEL_FILE, PROGRAM
+        )
+    };
+    format!(
+        "AI levels: {}, skill{} to skill{}{}",
+        levels.join(", "),
+        MIN_SKILL,
+        MAX_SKILL,
+        learned
+    )
+}
+
+/// Reads an agent: an AI level, an external engine or a heuristic profile file.
+fn agent(name: &str) -> Result<AgentSpec, CliError> {
+    AgentSpec::from_str(name)
+        .ok_or_else(|| CliError::Usage(format!("unknown agent: {}\n{}", name, agents_help())))
+}
+
+/// Reads an AI level.
+fn level(name: &str) -> Result<AILevel, CliError> {
+    AILevel::from_str(name)
+        .ok_or_else(|| CliError::Usage(format!("unknown AI level: {}\n{}", name, levels_help())))
+}
+
+/// Reads a number, `what` naming it in the error.
+fn number<T: FromStr>(value: &str, what: &str) -> Result<T, CliError> {
+    value
+        .parse::<T>()
+        .map_err(|_| CliError::Usage(format!("invalid {}: {}", what, value)))
+}
+
+/// Arguments of a command: its positional arguments and its options, by name.
+struct Arguments {
+    command: &'static Command,
+    positional: Vec<String>,
+    options: HashMap<&'static str, String>,
+}
+
+impl Arguments {
+    /// Splits the arguments of a command into positional arguments and options.
+    fn parse(command: &'static Command, args: &[String]) -> Result<Self, CliError> {
+        let mut arguments = Self {
+            command,
+            positional: Vec::new(),
+            options: HashMap::new(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") || arg == "--" {
+                arguments.positional.push(arg.clone());
+                continue;
+            }
+            let Some(&(name, value, _)) = command.options.iter().find(|(name, _, _)| name == arg)
+            else {
+                return Err(arguments.usage(&format!("unknown option: {}", arg)));
+            };
+            let value = if value.is_empty() {
+                String::new()
+            } else {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| arguments.usage(&format!("missing value for {}", name)))?
+            };
+            arguments.options.insert(name, value);
+        }
+        Ok(arguments)
+    }
+
+    /// Returns a usage error of the command.
+    fn usage(&self, message: &str) -> CliError {
+        CliError::Usage(format!(
+            "{}\nUsage: {} {} {}\nRun `{} help {}` for more information.",
+            message, PROGRAM, self.command.name, self.command.arguments, PROGRAM, self.command.name
+        ))
+    }
+
+    /// Checks that there are between `min` and `max` positional arguments.
+    fn expect(&self, min: usize, max: usize) -> Result<(), CliError> {
+        match self.positional.len() {
+            n if n < min => Err(self.usage("missing arguments")),
+            n if n > max => {
+                Err(self.usage(&format!("unexpected argument: {}", self.positional[max])))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).map(String::as_str)
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    fn path(&self, index: usize) -> &Path {
+        Path::new(&self.positional[index])
+    }
+
+    fn threads(&self) -> Result<usize, CliError> {
+        self.option("--threads")
+            .map_or(Ok(default_threads()), |threads| {
+                number(threads, "number of threads")
+            })
+    }
+}
+
+/// Runs the command given by the arguments, without the name of the program.
+pub fn run(args: &[String]) -> Result<Launch, CliError> {
+    let Some((name, args)) = args.split_first() else {
+        return Ok(Launch::Window(None));
+    };
+    if name == "--help" || name == "-h" {
+        println!("{}", help());
+        return Ok(Launch::Done);
+    }
+    let command = COMMANDS
+        .iter()
+        .find(|command| command.name == name)
+        .ok_or_else(|| {
+            CliError::Usage(format!(
+                "unknown command: {}\nRun `{} --help` for the list of commands.",
+                name, PROGRAM
+            ))
+        })?;
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", command_help(command));
+        return Ok(Launch::Done);
+    }
+    let arguments = Arguments::parse(command, args)?;
+    let failure =
+        |result: Result<(), String>| result.map(|()| Launch::Done).map_err(CliError::Failure);
+    match command.name {
+        "play" => {
+            arguments.expect(0, 0)?;
+            Ok(Launch::Window(None))
+        }
+        "fight" => fight(&arguments),
+        "analyze" => {
+            arguments.expect(1, 1)?;
+            let ai_level = arguments
+                .option("--level")
+                .map_or(Ok(AILevel::Hard), level)?;
+            failure(launch_analysis(&arguments.positional[0], ai_level))
+        }
+        "solve" => {
+            arguments.expect(1, 1)?;
+            failure(launch_solve(&arguments.positional[0]))
+        }
+        "perft" => {
+            arguments.expect(1, 2)?;
+            let depth = number(&arguments.positional[0], "depth")?;
+            let start = Morpion::new().to_notation();
+            failure(launch_perft(depth, arguments.get(1).unwrap_or(&start)))
+        }
+        "engine" => {
+            arguments.expect(0, 0)?;
+            launch_engine();
+            Ok(Launch::Done)
+        }
+        "replay" => {
+            arguments.expect(1, 1)?;
+            let game = arguments
+                .option("--game")
+                .map(|game| number(game, "game number"))
+                .transpose()?;
+            let mut replay = Replay::load(arguments.path(0), game).map_err(CliError::Failure)?;
+            if arguments.flag("--text") {
+                replay.print();
+                return Ok(Launch::Done);
+            }
+            Ok(Launch::Window(Some(Box::new(replay))))
+        }
+        "stats" => {
+            arguments.expect(1, 1)?;
+            failure(launch_game_stats(arguments.path(0)))
+        }
+        "tournament" => {
+            arguments.expect(5, usize::MAX)?;
+            let schedule = Schedule::from_str(&arguments.positional[0]).ok_or_else(|| {
+                arguments.usage(&format!("unknown schedule: {}", arguments.positional[0]))
+            })?;
+            let agents = arguments.positional[3..]
+                .iter()
+                .map(|name| agent(name))
+                .collect::<Result<Vec<_>, _>>()?;
+            failure(launch_tournament(
+                schedule,
+                &agents,
+                number(&arguments.positional[1], "number of games")?,
+                arguments.threads()?,
+                arguments.path(2),
+            ))
+        }
+        "sprt" => {
+            arguments.expect(4, 4)?;
+            let sprt = Sprt {
+                elo0: number(&arguments.positional[2], "elo0")?,
+                elo1: number(&arguments.positional[3], "elo1")?,
+                alpha: 0.05,
+                beta: 0.05,
+            };
+            let max_games = arguments
+                .option("--max-games")
+                .map_or(Ok(DEFAULT_SPRT_GAMES), |games| {
+                    number(games, "number of games")
+                })?;
+            launch_sprt(
+                agent(&arguments.positional[0])?,
+                agent(&arguments.positional[1])?,
+                sprt,
+                max_games,
+                arguments.threads()?,
+            );
+            Ok(Launch::Done)
+        }
+        "rate" => {
+            arguments.expect(1, 1)?;
+            let games = number(&arguments.positional[0], "number of games")?;
+            failure(launch_rating(
+                games,
+                arguments.threads()?,
+                Path::new(RATINGS_FILE),
+            ))
+        }
+        "tune" => {
+            arguments.expect(3, 3)?;
+            let tunable = Tunable::from_str(&arguments.positional[0]).ok_or_else(|| {
+                arguments.usage(&format!("unknown heuristic: {}", arguments.positional[0]))
+            })?;
+            let games = number(&arguments.positional[1], "number of games")?;
+            let records = arguments.option("--records").map(Path::new);
+            failure(launch_tuning(tunable, games, records, arguments.path(2)))
+        }
+        "train" => {
+            arguments.expect(3, 3)?;
+            let hidden = number(&arguments.positional[0], "number of hidden units")?;
+            let games = number(&arguments.positional[1], "number of games")?;
+            failure(launch_training(hidden, games, arguments.path(2)))
+        }
+        "book" => {
+            arguments.expect(3, 3)?;
+            let plies = number(&arguments.positional[0], "number of plies")?;
+            let width = number(&arguments.positional[1], "width")?;
+            failure(launch_book_generation(
+                plies,
+                width,
+                AILevel::Hard,
+                arguments.path(2),
+            ))
+        }
+        "codingame" => {
+            arguments.expect(0, 1)?;
+            let agent = arguments.get(0).map(agent).transpose()?;
+            failure(launch_codingame(agent))
+        }
+        "help" => {
+            arguments.expect(0, 1)?;
+            match arguments.get(0) {
+                None => println!("{}", help()),
+                Some(name) => {
+                    let command = COMMANDS
+                        .iter()
+                        .find(|command| command.name == name)
+                        .ok_or_else(|| arguments.usage(&format!("unknown command: {}", name)))?;
+                    println!("{}", command_help(command));
+                }
+            }
+            Ok(Launch::Done)
+        }
+        _ => unreachable!("every command is handled"),
+    }
+}
+
+/// Runs the `fight` command: a new series of fights, or the end of an interrupted one.
+fn fight(arguments: &Arguments) -> Result<Launch, CliError> {
+    let format = arguments
+        .option("--format")
+        .map(|format| {
+            Format::from_str(format)
+                .ok_or_else(|| arguments.usage(&format!("unknown format: {}", format)))
+        })
+        .transpose()?;
+    let openings = match (
+        arguments.option("--openings"),
+        arguments.option("--random-openings"),
+    ) {
+        (Some(_), Some(_)) => {
+            return Err(arguments.usage("--openings and --random-openings can't be used together"))
+        }
+        (Some(openings), None) => Some(Openings::Suite(PathBuf::from(openings))),
+        (None, Some(plies)) => Some(Openings::Random(number(plies, "number of plies")?)),
+        (None, None) => None,
+    };
+    let time_control = arguments
+        .option("--time")
+        .map(|time_control| {
+            TimeControl::from_str(time_control)
+                .ok_or_else(|| arguments.usage(&format!("invalid time control: {}", time_control)))
+        })
+        .transpose()?;
+    let options = FightOptions {
+        threads: arguments.threads()?,
+        seed: arguments
+            .option("--seed")
+            .map(|seed| number(seed, "seed"))
+            .transpose()?,
+        openings,
+        time_control,
+        format,
+        output: arguments.option("--output").map(PathBuf::from),
+        records: arguments.option("--records").map(PathBuf::from),
+        session: arguments.option("--session").map(PathBuf::from),
+    };
+    let result = match arguments.option("--resume") {
+        Some(session) => {
+            arguments.expect(0, 0)?;
+            resume_fights(Path::new(session), &options)
+        }
+        None => {
+            arguments.expect(2, 3)?;
+            let games = arguments
+                .get(2)
+                .map_or(Ok(DEFAULT_FIGHTS), |games| number(games, "number of games"))?;
+            launch_fights(
+                agent(&arguments.positional[0])?,
+                agent(&arguments.positional[1])?,
+                games,
+                &options,
+            )
+        }
+    };
+    result.map(|()| Launch::Done).map_err(CliError::Failure)
+}
@@ -0,0 +1,259 @@
+use std::io::{self, BufRead};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use crate::agent::{Agent, AgentError, AgentSpec, EngineConfig};
+use crate::ai::generate_moves;
+use crate::external::{Process, TIMEOUT_MARGIN};
+use crate::heuristic::profile;
+use crate::morpion::Morpion;
+use crate::search::{Limits, Move, Searcher};
+
+/// Thinking time of the first turn on CodinGame.
+const FIRST_TURN_TIME: Duration = Duration::from_millis(1000);
+/// Thinking time of the other turns on CodinGame.
+const TURN_TIME: Duration = Duration::from_millis(100);
+/// Time kept for reading the input and writing the answer.
+const SAFETY_MARGIN: Duration = Duration::from_millis(15);
+/// Size of the transposition tables of the default searcher, in megabytes.
+const HASH_SIZE: usize = 64;
+
+/// Converts a move `(ult_index, index)` into the `(row, col)` of the CodinGame grid.
+pub fn to_grid((ult_index, index): Move) -> (usize, usize) {
+    (ult_index / 3 * 3 + index / 3, ult_index % 3 * 3 + index % 3)
+}
+
+/// Converts the `(row, col)` of the CodinGame grid into a move `(ult_index, index)`.
+pub fn from_grid((row, col): (usize, usize)) -> Move {
+    (row / 3 * 3 + col / 3, row % 3 * 3 + col % 3)
+}
+
+/// Parses a `row col` line of the CodinGame protocol, `-1 -1` being no move.
+/// Returns `None` if the line is invalid.
+fn parse_grid(line: &str) -> Option<Option<Move>> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields[..] {
+        ["-1", "-1", ..] => Some(None),
+        [row, col, ..] => {
+            let row = row.parse::<usize>().ok().filter(|row| *row < 9)?;
+            let col = col.parse::<usize>().ok().filter(|col| *col < 9)?;
+            Some(Some(from_grid((row, col))))
+        }
+        _ => None,
+    }
+}
+
+/// Writes a move as a `row col` line of the CodinGame protocol.
+fn format_grid(m: Move) -> String {
+    let (row, col) = to_grid(m);
+    format!("{} {}", row, col)
+}
+
+/// Finds the move leading from `previous` to `morpion`.
+/// Returns `Some(None)` if both are the starting position, and `None` if `morpion` does not follow `previous`.
+fn move_between(previous: &Morpion, morpion: &Morpion) -> Option<Option<Move>> {
+    if previous.ply() == 0 && morpion.ply() == 0 {
+        return Some(None);
+    }
+    generate_moves(previous)
+        .into_iter()
+        .find(|&(ult_index, index)| {
+            let mut child = previous.clone();
+            child.play_at(ult_index, index);
+            child.hash() == morpion.hash()
+        })
+        .map(Some)
+}
+
+/// An external bot written for CodinGame, playing through its protocol.
+/// These bots keep the game in memory, so they can only play games from the start.
+pub struct CodinGameBot {
+    config: EngineConfig,
+    process: Process,
+    /// Position after the last move of the bot, `None` before its first turn.
+    previous: Option<Morpion>,
+}
+
+impl CodinGameBot {
+    /// Launches the program of the bot.
+    pub fn launch(config: &EngineConfig) -> Result<Self, AgentError> {
+        Ok(Self {
+            config: config.clone(),
+            process: Process::spawn(config)?,
+            previous: None,
+        })
+    }
+}
+
+impl Agent for CodinGameBot {
+    fn play(&mut self, morpion: &Morpion, stop: &AtomicBool) -> Result<Option<Move>, AgentError> {
+        let last_move = match self
+            .previous
+            .as_ref()
+            .and_then(|previous| move_between(previous, morpion))
+        {
+            Some(last_move) => last_move,
+            None => {
+                // A new game, which needs a new bot if this one already played
+                let last_move = move_between(&Morpion::new(), morpion).ok_or_else(|| {
+                    AgentError::Protocol(format!(
+                        "{} can't start from {}",
+                        self.config.name,
+                        morpion.to_notation()
+                    ))
+                })?;
+                if self.previous.take().is_some() {
+                    self.process = Process::spawn(&self.config)?;
+                }
+                last_move
+            }
+        };
+        let turn_time = if self.previous.is_none() {
+            self.config.movetime.max(FIRST_TURN_TIME)
+        } else {
+            self.config.movetime
+        };
+        self.process
+            .send(&last_move.map_or("-1 -1".to_string(), format_grid))?;
+        let moves = generate_moves(morpion);
+        self.process.send(&moves.len().to_string())?;
+        for m in moves {
+            self.process.send(&format_grid(m))?;
+        }
+        let Some(line) = self.process.read_line(turn_time + TIMEOUT_MARGIN, stop)? else {
+            // The bot can't be interrupted, and its answer would be out of sync
+            self.process = Process::spawn(&self.config)?;
+            self.previous = None;
+            return Ok(None);
+        };
+        let Some(Some((ult_index, index))) = parse_grid(&line) else {
+            return Err(AgentError::Protocol(line));
+        };
+        if morpion.index_is_playable(ult_index, index) {
+            let mut child = morpion.clone();
+            child.play_at(ult_index, index);
+            self.previous = Some(child);
+        }
+        Ok(Some((ult_index, index)))
+    }
+
+    fn is_timed(&self) -> bool {
+        true
+    }
+}
+
+/// Reads the next line of stdin, `None` once it is closed.
+fn read_line(lines: &mut impl Iterator<Item = io::Result<String>>) -> Option<String> {
+    lines.next().and_then(Result::ok)
+}
+
+/// Plays a game on stdin and stdout with the protocol of the _Ultimate Tic-Tac-Toe_ bots of CodinGame.
+/// Each turn, the bot reads the last move of the opponent (`-1 -1` if it starts), the number of valid moves
+/// and one line per valid move, then answers its move. Moves are written `row col` on the whole 9x9 grid.
+/// The rules are the same, except that CodinGame gives a full board to the player with the most small boards.
+///
+/// With no agent, an iterative deepening search using the heuristic profile plays within the time limits of CodinGame,
+/// otherwise the agent plays without any time limit.
+pub fn launch_codingame(agent: Option<AgentSpec>) -> Result<(), String> {
+    let mut agent = agent
+        .map(|agent| agent.build())
+        .transpose()
+        .map_err(|e| format!("can't start the agent: {}", e))?;
+    let mut searcher = Searcher::new(HASH_SIZE, 1);
+    let never = AtomicBool::new(false);
+    let mut morpion = Morpion::new();
+    let mut turn_time = FIRST_TURN_TIME;
+    let mut lines = io::stdin().lock().lines();
+    while let Some(line) = read_line(&mut lines) {
+        let Some(last_move) = parse_grid(&line) else {
+            return Err(format!("invalid move: {}", line));
+        };
+        if let Some((ult_index, index)) = last_move {
+            if !morpion.index_is_playable(ult_index, index) {
+                return Err(format!("illegal move: {}", line));
+            }
+            morpion.play_at(ult_index, index);
+        }
+        let Some(count) = read_line(&mut lines).and_then(|line| line.trim().parse::<usize>().ok())
+        else {
+            return Err("invalid number of valid moves".to_string());
+        };
+        let valid_moves: Vec<Move> = (0..count)
+            .filter_map(|_| {
+                read_line(&mut lines)
+                    .as_deref()
+                    .and_then(parse_grid)
+                    .flatten()
+            })
+            .collect();
+        let best_move = match agent.as_mut() {
+            Some(agent) => agent.play(&morpion, &never).unwrap_or_else(|e| {
+                eprintln!("the agent failed: {}", e);
+                None
+            }),
+            None => {
+                let limits = Limits {
+                    movetime: Some(turn_time - SAFETY_MARGIN),
+                    ..Limits::default()
+                };
+                searcher.search(
+                    &morpion,
+                    &profile().heuristic,
+                    limits,
+                    &AtomicBool::new(false),
+                    |_| {},
+                )
+            }
+        };
+        // The valid moves of CodinGame have the last word
+        let Some(best_move) = best_move
+            .filter(|m| valid_moves.contains(m))
+            .or_else(|| valid_moves.first().copied())
+        else {
+            return Err("no valid move".to_string());
+        };
+        morpion.play_at(best_move.0, best_move.1);
+        println!("{}", format_grid(best_move));
+        turn_time = TURN_TIME;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_round_trip() {
+        assert_eq!(to_grid((0, 0)), (0, 0));
+        assert_eq!(to_grid((2, 5)), (1, 8));
+        assert_eq!(to_grid((7, 3)), (7, 3));
+        assert_eq!(to_grid((8, 8)), (8, 8));
+        for ult_index in 0..9 {
+            for index in 0..9 {
+                assert_eq!(from_grid(to_grid((ult_index, index))), (ult_index, index));
+            }
+        }
+    }
+
+    #[test]
+    fn grid_lines() {
+        assert_eq!(parse_grid("-1 -1"), Some(None));
+        assert_eq!(parse_grid("1 8"), Some(Some((2, 5))));
+        assert_eq!(parse_grid("9 0"), None);
+        assert_eq!(parse_grid("3"), None);
+        assert_eq!(format_grid((2, 5)), "1 8");
+    }
+
+    #[test]
+    fn move_between_finds_the_move() {
+        let start = Morpion::new();
+        assert_eq!(move_between(&start, &start), Some(None));
+        let mut morpion = start.clone();
+        morpion.play_at(4, 2);
+        assert_eq!(move_between(&start, &morpion), Some(Some((4, 2))));
+        let mut other = start.clone();
+        other.play_at(4, 3);
+        assert_eq!(move_between(&morpion, &other), None);
+    }
+}
@@ -8,6 +8,19 @@ pub const SCREEN_SIZE: (f32, f32) = (
     (CELL_SIZE * 9.0) + (6.0 * CELL_PADDING) + (3.0 * BORDER_PADDING),
 );
 
+/// Horizontal position and width of the evaluation bar, left of the board.
+pub const EVAL_BAR_X: f32 = BORDER_PADDING / 2.0 - EVAL_BAR_WIDTH / 2.0;
+pub const EVAL_BAR_WIDTH: f32 = 12.0;
+/// Position and size (x, y, width, height) of the hint button, under the board.
+pub const HINT_BUTTON: (f32, f32, f32, f32) = (
+    SCREEN_SIZE.0 - BORDER_PADDING - 120.0,
+    SCREEN_SIZE.1 - BORDER_PADDING - 5.0,
+    120.0,
+    30.0,
+);
+/// Number of moves suggested by a hint.
+pub const HINT_MOVES: usize = 3;
+
 pub const CROSS_CIRCLE_SCALE_FACTOR: f32 = 0.30612245;
 
 pub const DESIRED_FPS: u32 = 15;
@@ -0,0 +1,291 @@
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::heuristic::{profile, Profile};
+use crate::morpion::{format_move, parse_move, Morpion};
+use crate::search::{Limits, Searcher};
+
+/// Default size of the transposition tables, in megabytes.
+const DEFAULT_HASH: usize = 16;
+/// Maximum size of the transposition tables, in megabytes.
+const MAX_HASH: usize = 4096;
+/// Maximum number of search threads.
+const MAX_THREADS: usize = 64;
+
+/// A text engine protocol modelled on UCI, read on stdin and answered on stdout.
+///
+/// | command | answer |
+/// |---|---|
+/// | `uci` | `id` and `option` lines, then `uciok` |
+/// | `isready` | `readyok` |
+/// | `setoption name <Profile\|Hash\|Threads> value <value>` | |
+/// | `ucinewgame` | |
+/// | `position <startpos\|notation <notation>> [moves <move>...]` | |
+/// | `go [depth <plies>] [movetime <ms>] [nodes <n>] [infinite]` | `info` lines, then `bestmove <move>` |
+/// | `stop` | `bestmove <move>` of the running search |
+/// | `quit` | |
+///
+/// Moves are written with [`format_move`] and positions with [`Morpion::to_notation`].
+/// Errors are reported on `info string` lines.
+pub struct Engine {
+    position: Morpion,
+    profile: Profile,
+    /// The searcher, lent to the thread of the running search if any.
+    searcher: Option<Searcher>,
+    /// Stop flag and thread of the running search.
+    search: Option<(Arc<AtomicBool>, JoinHandle<Searcher>)>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            position: Morpion::new(),
+            profile: profile().clone(),
+            searcher: Some(Searcher::new(DEFAULT_HASH, 1)),
+            search: None,
+        }
+    }
+
+    /// Stops the running search, if any, and waits for its thread: its best move is still sent.
+    fn stop(&mut self) {
+        if let Some((stop, handle)) = self.search.take() {
+            stop.store(true, Ordering::Relaxed);
+            self.searcher = Some(handle.join().expect("search thread panicked"));
+        }
+    }
+
+    /// Returns the searcher, stopping the running search if any.
+    fn searcher(&mut self) -> &mut Searcher {
+        self.stop();
+        self.searcher.as_mut().unwrap()
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let parse_count = |max: usize| {
+            value
+                .parse::<usize>()
+                .ok()
+                .filter(|v| (1..=max).contains(v))
+                .ok_or_else(|| format!("invalid value for {}: {}", name, value))
+        };
+        match name.to_lowercase().as_str() {
+            "profile" => {
+                self.stop();
+                self.profile = if value.is_empty() {
+                    profile().clone()
+                } else {
+                    Profile::load(Path::new(value))
+                        .map_err(|e| format!("can't load {}: {}", value, e))?
+                };
+            }
+            "hash" => {
+                let hash_size = parse_count(MAX_HASH)?;
+                self.searcher().set_hash_size(hash_size);
+            }
+            "threads" => {
+                let threads = parse_count(MAX_THREADS)?;
+                self.searcher().set_threads(threads);
+            }
+            _ => return Err(format!("unknown option: {}", name)),
+        }
+        Ok(())
+    }
+
+    /// Parses the arguments of `position`.
+    fn parse_position(args: &[&str]) -> Result<Morpion, String> {
+        let (mut morpion, rest) = match args {
+            ["startpos", rest @ ..] => (Morpion::new(), rest),
+            ["notation", boards, player, focus, rest @ ..] => (
+                Morpion::from_notation(&format!("{} {} {}", boards, player, focus))?,
+                rest,
+            ),
+            _ => return Err("expected startpos or notation".to_string()),
+        };
+        match rest {
+            [] => {}
+            ["moves", moves @ ..] => {
+                for m in moves {
+                    let (ult_index, index) = parse_move(m)?;
+                    if morpion.is_over() || !morpion.index_is_playable(ult_index, index) {
+                        return Err(format!("illegal move: {}", m));
+                    }
+                    morpion.play_at(ult_index, index);
+                }
+            }
+            _ => return Err(format!("unexpected argument: {}", rest[0])),
+        }
+        Ok(morpion)
+    }
+
+    /// Parses the arguments of `go`, searching up to the depth of the profile if no limit is given.
+    fn parse_limits(&self, args: &[&str]) -> Result<Limits, String> {
+        let mut limits = Limits::default();
+        let mut infinite = false;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if *arg == "infinite" {
+                infinite = true;
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", arg))?;
+            let value = value
+                .parse::<u64>()
+                .map_err(|e| format!("invalid value for {}: {}", arg, e))?;
+            match *arg {
+                "depth" => limits.depth = Some(value as isize),
+                "movetime" => limits.movetime = Some(Duration::from_millis(value)),
+                "nodes" => limits.nodes = Some(value as usize),
+                _ => return Err(format!("unknown limit: {}", arg)),
+            }
+        }
+        if !infinite
+            && limits.depth.is_none()
+            && limits.movetime.is_none()
+            && limits.nodes.is_none()
+        {
+            limits.depth = Some(self.profile.depth);
+        }
+        Ok(limits)
+    }
+
+    /// Starts searching the current position in the background.
+    fn go(&mut self, limits: Limits) {
+        self.stop();
+        let mut searcher = self.searcher.take().unwrap();
+        let position = self.position.clone();
+        let heuristic = self.profile.heuristic.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = thread::spawn(move || {
+            let best_move = searcher.search(&position, &heuristic, limits, &thread_stop, |info| {
+                println!("{}", info)
+            });
+            match best_move {
+                Some(best_move) => println!("bestmove {}", format_move(best_move)),
+                None => println!("bestmove none"),
+            }
+            searcher
+        });
+        self.search = Some((stop, handle));
+    }
+
+    /// Executes one command, returns `false` when the engine must quit.
+    pub fn execute(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let result = match tokens[..] {
+            [] => Ok(()),
+            ["uci"] => {
+                println!("id name ultimate-morpion");
+                println!("id author lilBchii");
+                println!("option name Profile type string default <empty>");
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH, MAX_HASH
+                );
+                println!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                );
+                println!("uciok");
+                Ok(())
+            }
+            ["isready"] => {
+                println!("readyok");
+                Ok(())
+            }
+            ["setoption", "name", name, "value", ref value @ ..] => {
+                self.set_option(name, &value.join(" "))
+            }
+            ["setoption", "name", name] => self.set_option(name, ""),
+            ["ucinewgame"] => {
+                self.searcher().clear();
+                self.position = Morpion::new();
+                Ok(())
+            }
+            ["position", ref args @ ..] => {
+                Self::parse_position(args).map(|position| self.position = position)
+            }
+            ["go", ref args @ ..] => self.parse_limits(args).map(|limits| self.go(limits)),
+            ["stop"] => {
+                self.stop();
+                Ok(())
+            }
+            ["quit"] => {
+                self.stop();
+                return false;
+            }
+            _ => Err(format!("unknown command: {}", line.trim())),
+        };
+        if let Err(e) = result {
+            println!("info string {}", e);
+        }
+        true
+    }
+}
+
+/// Runs the engine protocol on stdin/stdout until `quit` or the end of the input.
+pub fn launch_engine() {
+    let mut engine = Engine::new();
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !engine.execute(&line) {
+            return;
+        }
+    }
+    engine.stop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_position_plays_the_moves() {
+        let morpion = Engine::parse_position(&["startpos", "moves", "40", "04"]).unwrap();
+        let mut expected = Morpion::new();
+        expected.play_at(4, 0);
+        expected.play_at(0, 4);
+        assert_eq!(morpion.to_notation(), expected.to_notation());
+
+        let notation = expected.to_notation();
+        let parts: Vec<&str> = notation.split(' ').collect();
+        let morpion = Engine::parse_position(&["notation", parts[0], parts[1], parts[2]]).unwrap();
+        assert_eq!(morpion.to_notation(), notation);
+    }
+
+    #[test]
+    fn parse_position_rejects_bad_input() {
+        assert!(Engine::parse_position(&["startpos", "moves", "40", "41"]).is_err());
+        assert!(Engine::parse_position(&["startpos", "40"]).is_err());
+        assert!(Engine::parse_position(&["fen"]).is_err());
+    }
+
+    #[test]
+    fn parse_limits_reads_the_go_arguments() {
+        let engine = Engine::new();
+        let limits = engine
+            .parse_limits(&["depth", "3", "movetime", "100", "nodes", "5000"])
+            .unwrap();
+        assert_eq!(limits.depth, Some(3));
+        assert_eq!(limits.movetime, Some(Duration::from_millis(100)));
+        assert_eq!(limits.nodes, Some(5000));
+
+        let limits = engine.parse_limits(&[]).unwrap();
+        assert_eq!(limits.depth, Some(engine.profile.depth));
+
+        let limits = engine.parse_limits(&["infinite"]).unwrap();
+        assert!(limits.depth.is_none() && limits.movetime.is_none() && limits.nodes.is_none());
+
+        assert!(engine.parse_limits(&["depth"]).is_err());
+        assert!(engine.parse_limits(&["depth", "x"]).is_err());
+        assert!(engine.parse_limits(&["mate", "2"]).is_err());
+    }
+}
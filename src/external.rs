@@ -0,0 +1,236 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::agent::{Agent, AgentError, EngineConfig};
+use crate::ai::Evaluation;
+use crate::morpion::{parse_move, Morpion, Player, PlayingState};
+
+/// Time given to an engine to start and answer `uciok` and `readyok`.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Extra time allowed over the thinking time, for the communication.
+pub const TIMEOUT_MARGIN: Duration = Duration::from_millis(500);
+/// Time given to a program that closed its output to exit.
+const EXIT_TIMEOUT: Duration = Duration::from_millis(200);
+/// How often the stop flag is checked while waiting for the engine.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A running external program, which we talk to line by line.
+/// It is killed when dropped.
+pub struct Process {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines written by the program, read by a background thread.
+    lines: Receiver<String>,
+}
+
+impl Process {
+    /// Launches the program of the engine.
+    pub fn spawn(config: &EngineConfig) -> Result<Self, AgentError> {
+        let mut child = Command::new(&config.command[0])
+            .args(&config.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| AgentError::Crashed(format!("can't launch {}: {}", config.name, e)))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            name: config.name.clone(),
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    /// Sends a line to the program.
+    pub fn send(&mut self, line: &str) -> Result<(), AgentError> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| self.crashed())
+    }
+
+    /// Describes how the program ended, once it stopped answering.
+    fn crashed(&mut self) -> AgentError {
+        // The program may still be exiting
+        let deadline = Instant::now() + EXIT_TIMEOUT;
+        let status = loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => break status.to_string(),
+                Ok(None) if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+                _ => break "output closed".to_string(),
+            }
+        };
+        AgentError::Crashed(format!("{} {}", self.name, status))
+    }
+
+    /// Waits for the next line written by the program.
+    /// Returns `Ok(None)` if `stop` was set.
+    pub fn read_line(
+        &mut self,
+        timeout: Duration,
+        stop: &AtomicBool,
+    ) -> Result<Option<String>, AgentError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(AgentError::Timeout(timeout));
+            }
+            match self.lines.recv_timeout(POLL_INTERVAL.min(deadline - now)) {
+                Ok(line) => return Ok(Some(line)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Err(self.crashed()),
+            }
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// An external program playing through the engine protocol (see [`crate::engine`]).
+pub struct ExternalEngine {
+    process: Process,
+    movetime: Duration,
+    /// Last `info` line with a score sent during the current search.
+    last_info: Option<String>,
+    evaluation: Option<Evaluation>,
+}
+
+/// Reads the score of an `info` line, from the point of view of `player` who is to move.
+fn parse_info_score(line: &str, player: Player) -> Option<Evaluation> {
+    let mut tokens = line
+        .split_whitespace()
+        .skip_while(|token| *token != "score")
+        .skip(1);
+    let kind = tokens.next()?;
+    let value = tokens.next()?.parse::<isize>().ok()?;
+    match kind {
+        "cp" => Some(Evaluation::Score(match player {
+            Player::X => value,
+            Player::O => value.saturating_neg(),
+        })),
+        "mate" if value >= 0 => Some(Evaluation::Proven(
+            PlayingState::Win(player),
+            value as usize,
+        )),
+        "mate" => Some(Evaluation::Proven(
+            PlayingState::Win(player.other()),
+            value.unsigned_abs(),
+        )),
+        _ => None,
+    }
+}
+
+impl ExternalEngine {
+    /// Launches the program of the engine and waits until it is ready.
+    pub fn launch(config: &EngineConfig) -> Result<Self, AgentError> {
+        let mut engine = Self {
+            process: Process::spawn(config)?,
+            movetime: config.movetime,
+            last_info: None,
+            evaluation: None,
+        };
+        engine.process.send("uci")?;
+        engine.wait_for("uciok", STARTUP_TIMEOUT, &AtomicBool::new(false))?;
+        engine.process.send("isready")?;
+        engine.wait_for("readyok", STARTUP_TIMEOUT, &AtomicBool::new(false))?;
+        Ok(engine)
+    }
+
+    /// Waits for a line starting with `prefix` and returns it, ignoring the others
+    /// except the last `info` line with a score.
+    /// Returns `Ok(None)` if `stop` was set.
+    fn wait_for(
+        &mut self,
+        prefix: &str,
+        timeout: Duration,
+        stop: &AtomicBool,
+    ) -> Result<Option<String>, AgentError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.process.read_line(remaining, stop) {
+                Ok(Some(line)) if !line.starts_with(prefix) => {
+                    if line.starts_with("info") && line.contains(" score ") {
+                        self.last_info = Some(line);
+                    }
+                }
+                Err(AgentError::Timeout(_)) => return Err(AgentError::Timeout(timeout)),
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Agent for ExternalEngine {
+    fn play(
+        &mut self,
+        morpion: &Morpion,
+        stop: &AtomicBool,
+    ) -> Result<Option<(usize, usize)>, AgentError> {
+        self.last_info = None;
+        self.process
+            .send(&format!("position notation {}", morpion.to_notation()))?;
+        self.process
+            .send(&format!("go movetime {}", self.movetime.as_millis()))?;
+        let Some(line) = self.wait_for("bestmove", self.movetime + TIMEOUT_MARGIN, stop)? else {
+            // The answer to the aborted search must not be taken for the next one
+            self.process.send("stop")?;
+            self.wait_for("bestmove", TIMEOUT_MARGIN, &AtomicBool::new(false))?;
+            return Ok(None);
+        };
+        let best_move = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|best_move| parse_move(best_move).ok())
+            .ok_or(AgentError::Protocol(line))?;
+        self.evaluation = self
+            .last_info
+            .as_deref()
+            .and_then(|info| parse_info_score(info, morpion.player));
+        Ok(Some(best_move))
+    }
+
+    fn evaluation(&self) -> Option<Evaluation> {
+        self.evaluation.clone()
+    }
+
+    fn is_timed(&self) -> bool {
+        true
+    }
+
+    fn set_movetime(&mut self, movetime: Duration) {
+        self.movetime = movetime;
+    }
+}
+
+impl Drop for ExternalEngine {
+    fn drop(&mut self) {
+        let _ = self.process.send("quit");
+    }
+}
@@ -1,54 +1,565 @@
-use crate::ai::AILevel;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::agent::{checked_move, Agent, AgentError, AgentSpec};
+use crate::ai::Evaluation;
 use crate::morpion::PlayingState::Win;
 use crate::morpion::{Morpion, Player, PlayingState};
+use crate::opening::{load_openings, random_opening};
+use crate::random;
+use crate::record::{record_name, save_record};
+use crate::report::{report, Format};
+use crate::search::Move;
+use crate::session::{append_game, load_session, save_session, Session};
+use crate::stats::{print_elo, Score, ThinkingTime};
+
+/// Width of the progress line, so the next line fully overwrites it.
+const PROGRESS_WIDTH: usize = 72;
+/// Number of moves the remaining time of a clock is shared between, when telling an agent its time for a move.
+const MOVES_TO_GO: u32 = 20;
+/// Time kept out of the time an agent is told it has for a move, for the exchanges with it and the end of
+/// its search, so that it doesn't lose on time by using all of it.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// A game played by two agents.
+#[derive(Clone, Debug)]
+pub struct Game {
+    /// Names of the agents, see [`AgentSpec::name`]: records stay readable when an agent is gone.
+    pub x_agent: String,
+    pub o_agent: String,
+    /// Position the game started from, written with [`Morpion::to_notation`], if not the start.
+    pub start: Option<String>,
+    /// Seed of the random choices of the AI levels, see [`random::seed`].
+    pub seed: u64,
+    pub time_control: Option<TimeControl>,
+    pub moves: Vec<Move>,
+    /// Thinking time of each move.
+    pub times: Vec<Duration>,
+    /// Evaluation of the position by the agent who played each move, if it gave one.
+    pub evaluations: Vec<Option<Evaluation>>,
+    pub result: PlayingState,
+    /// The player who lost the game without finishing it, and why.
+    pub forfeit: Option<(Player, AgentError)>,
+}
+
+impl Game {
+    /// Position the game started from.
+    pub fn start_position(&self) -> Morpion {
+        self.start
+            .as_ref()
+            .and_then(|start| Morpion::from_notation(start).ok())
+            .unwrap_or_else(Morpion::new)
+    }
+
+    /// Thinking times of the moves of a player, who may not be the first to move from the start position.
+    pub fn times(&self, player: Player) -> impl Iterator<Item = &Duration> {
+        let first = if self.start_position().player == player {
+            0
+        } else {
+            1
+        };
+        self.times.iter().skip(first).step_by(2)
+    }
+
+    /// Total thinking time of a player.
+    pub fn time(&self, player: Player) -> Duration {
+        self.times(player).sum()
+    }
+}
+
+impl std::fmt::Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (X) vs {} (O): {:?}",
+            self.x_agent, self.o_agent, self.result
+        )?;
+        if let Some((player, reason)) = &self.forfeit {
+            write!(f, ", {} forfeits: {}", player, reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the games of a series of fights start from.
+#[derive(Clone, Debug)]
+pub enum Openings {
+    /// The positions of an opening suite file, see [`crate::opening::parse_openings`].
+    Suite(PathBuf),
+    /// Positions reached by playing this many random moves.
+    Random(usize),
+}
+
+/// Time an agent may think during a game. Only external programs are held to it, see [`Agent::is_timed`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeControl {
+    /// The same time for every move.
+    PerMove(Duration),
+    /// A time for the whole game, plus an increment after each move.
+    Clock { base: Duration, increment: Duration },
+}
+
+impl TimeControl {
+    /// Converts a string into a [`TimeControl`]: `<ms>` per move, or `<base_ms>+<increment_ms>` per game.
+    /// Returns `None` if the input string is not a time control.
+    pub fn from_str(s: &str) -> Option<Self> {
+        let millis = |ms: &str| ms.parse::<u64>().ok().map(Duration::from_millis);
+        match s.split_once('+') {
+            Some((base, increment)) => Some(TimeControl::Clock {
+                base: millis(base)?,
+                increment: millis(increment)?,
+            }),
+            None => millis(s).map(TimeControl::PerMove),
+        }
+    }
+}
+
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeControl::PerMove(movetime) => write!(f, "{}", movetime.as_millis()),
+            TimeControl::Clock { base, increment } => {
+                write!(f, "{}+{}", base.as_millis(), increment.as_millis())
+            }
+        }
+    }
+}
+
+/// Options of a series of fights.
+#[derive(Clone, Debug)]
+pub struct FightOptions {
+    pub threads: usize,
+    /// Seed of the first game, the next games using the following seeds. Random if `None`.
+    pub seed: Option<u64>,
+    /// Openings of the games, each one played twice with the sides swapped. From the start if `None`.
+    pub openings: Option<Openings>,
+    /// Time control of the games, unlimited time if `None`.
+    pub time_control: Option<TimeControl>,
+    /// Format of the machine-readable report, if any.
+    pub format: Option<Format>,
+    /// File where the report is written, stdout if `None`: the text output is then left out.
+    pub output: Option<PathBuf>,
+    /// Directory where every game is saved as a game record, if any.
+    pub records: Option<PathBuf>,
+    /// Session file where the games are saved as they finish, so the fights can be resumed, if any.
+    pub session: Option<PathBuf>,
+}
 
 /// Launches a series of AI vs AI fights.
-/// Simulates `n` games between two AI levels and prints the results.
-pub fn launch_fights(x_level: AILevel, o_level: AILevel, n: usize) {
-    let mut f = n;
-    let mut x_win = 0;
-    let mut o_win = 0;
-    let mut tie = 0;
-    while f > 0 {
-        println!("fight {} (X {:?} - O {:?}):", n - f + 1, x_level, o_level);
-        let fight_result = fight(x_level, o_level);
-        println!("{:?}", fight_result);
-        match fight_result {
-            Win(player) => {
-                if player == Player::X {
-                    x_win += 1;
-                } else {
-                    o_win += 1;
+/// Simulates `n` games between two agents (AI levels or external engines) and prints the results.
+/// Without openings, `x_agent` always plays `X`, otherwise the agents swap sides after each game.
+pub fn launch_fights(
+    x_agent: AgentSpec,
+    o_agent: AgentSpec,
+    n: usize,
+    options: &FightOptions,
+) -> Result<(), String> {
+    let session = Session {
+        x_agent: x_agent.name(),
+        o_agent: o_agent.name(),
+        games: n,
+        seed: options.seed.unwrap_or_else(rand::random),
+        openings: options.openings.clone(),
+        time_control: options.time_control,
+    };
+    check_session(&session, x_agent, o_agent)?;
+    if let Some(path) = &options.session {
+        save_session(path, &session, &[])
+            .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+    }
+    play_session(&session, x_agent, o_agent, Vec::new(), options)
+}
+
+/// Resumes an interrupted series of fights from its session file, playing the games it has left
+/// and printing the results of the whole series.
+/// The agents, seed, openings and time control of the session replace those of `options`.
+pub fn resume_fights(path: &Path, options: &FightOptions) -> Result<(), String> {
+    let (session, games) =
+        load_session(path).map_err(|e| format!("can't resume {}: {}", path.display(), e))?;
+    let agent = |name: &str| {
+        AgentSpec::from_str(name).ok_or(format!(
+            "can't resume {}: unknown agent {}",
+            path.display(),
+            name
+        ))
+    };
+    let (x_agent, o_agent) = (agent(&session.x_agent)?, agent(&session.o_agent)?);
+    check_session(&session, x_agent, o_agent)?;
+    // A game interrupted while being written is dropped from the file
+    save_session(path, &session, &games)
+        .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+    let options = FightOptions {
+        session: Some(path.to_path_buf()),
+        ..options.clone()
+    };
+    play_session(&session, x_agent, o_agent, games, &options)
+}
+
+/// Checks that the games of a session can be played: with openings, each one is played twice with the sides
+/// swapped, and CodinGame bots can only play from the start.
+fn check_session(session: &Session, x_agent: AgentSpec, o_agent: AgentSpec) -> Result<(), String> {
+    if session.openings.is_none() {
+        return Ok(());
+    }
+    if session.games % 2 == 1 {
+        return Err(format!(
+            "with openings, the number of games must be even to play each opening twice, not {}",
+            session.games
+        ));
+    }
+    for agent in [x_agent, o_agent] {
+        if !agent.plays_openings() {
+            return Err(format!(
+                "{} can only play from the start, not from openings",
+                agent.name()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Plays the games of a session between its agents `x_agent` and `o_agent` left after the `played` ones,
+/// then prints the results of the whole series.
+fn play_session(
+    session: &Session,
+    x_agent: AgentSpec,
+    o_agent: AgentSpec,
+    played: Vec<Game>,
+    options: &FightOptions,
+) -> Result<(), String> {
+    let (n, seed) = (session.games, session.seed);
+    let quiet = options.format.is_some() && options.output.is_none();
+    let start = Instant::now();
+    let openings = match &session.openings {
+        None => vec![Morpion::new()],
+        Some(Openings::Suite(path)) => {
+            load_openings(path).map_err(|e| format!("can't load {}: {}", path.display(), e))?
+        }
+        Some(Openings::Random(plies)) => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..n.div_ceil(2))
+                .map(|_| random_opening(*plies, &mut rng))
+                .collect()
+        }
+    };
+    let swapped = |index: usize| session.openings.is_some() && index % 2 == 1;
+    let pairings: Vec<Pairing> = (0..n)
+        .map(|index| {
+            let (x, o) = if swapped(index) {
+                (o_agent, x_agent)
+            } else {
+                (x_agent, o_agent)
+            };
+            Pairing {
+                x_agent: x,
+                o_agent: o,
+                start: openings[index / 2 % openings.len()].clone(),
+                time_control: session.time_control,
+            }
+        })
+        .collect();
+    if let Some(records) = &options.records {
+        if let Err(e) = fs::create_dir_all(records) {
+            println!("can't create {}: {}", records.display(), e);
+        }
+    }
+    // Results of the first agent, whatever its side
+    let mut score = Score::default();
+    let (mut x_time, mut o_time) = (ThinkingTime::default(), ThinkingTime::default());
+    for (index, game) in played.iter().enumerate() {
+        let player = if swapped(index) { Player::O } else { Player::X };
+        score.add(&game.result, player);
+        x_time.add(game, player);
+        o_time.add(game, player.other());
+    }
+    let first = played.len();
+    let new_games = fight_all(
+        &pairings[first..],
+        seed.wrapping_add(first as u64),
+        options.threads,
+        |index, game| {
+            let index = first + index;
+            let player = if swapped(index) { Player::O } else { Player::X };
+            score.add(&game.result, player);
+            x_time.add(game, player);
+            o_time.add(game, player.other());
+            if let Some(path) = &options.session {
+                if let Err(e) = append_game(path, index, game) {
+                    println!("can't write {}: {}", path.display(), e);
                 }
             }
-            _ => {
-                tie += 1;
+            if let Some(records) = &options.records {
+                let path = records.join(record_name(index, n));
+                if let Err(e) = save_record(game, &path) {
+                    println!("can't write {}: {}", path.display(), e);
+                }
+            }
+            if quiet {
+                return;
+            }
+            let done = index + 1;
+            let remaining = start
+                .elapsed()
+                .mul_f64((n - done) as f64 / (done - first) as f64);
+            print!(
+                "\r{:<width$}\n\r{}/{} games, {} {}, {}s left",
+                format!("fight {}: {}", done, game),
+                done,
+                n,
+                x_agent.name(),
+                score,
+                remaining.as_secs(),
+                width = PROGRESS_WIDTH
+            );
+            let _ = io::stdout().flush();
+        },
+    );
+    let games: Vec<Game> = played.into_iter().chain(new_games).collect();
+    let mut result = Ok(());
+    if let Some(format) = options.format {
+        let text = report(format, &games, &x_agent.name());
+        match &options.output {
+            Some(output) => {
+                result = fs::write(output, text)
+                    .map_err(|e| format!("can't write {}: {}", output.display(), e));
             }
+            None => print!("{}", text),
         }
-        f -= 1;
     }
+    if quiet {
+        return result;
+    }
+    println!("\r{:<width$}", "", width = PROGRESS_WIDTH);
 
     let total = n as f32;
-    let x_stats = x_win as f32 / total * 100.0;
-    let o_stats = o_win as f32 / total * 100.0;
-    let tie_stats = tie as f32 / total * 100.0;
-    println!(
-        "-- fights results (total {}) -- \n=> X win ({:?}): {} ({}%)\n=> O win ({:?}): {} ({}%)\n=> tie: {} ({}%)",
-        n, x_level, x_win, x_stats, o_level, o_win, o_stats, tie, tie_stats
-    );
+    let x_stats = score.wins as f32 / total * 100.0;
+    let o_stats = score.losses as f32 / total * 100.0;
+    let tie_stats = score.draws as f32 / total * 100.0;
+    if session.openings.is_some() {
+        println!(
+            "-- fights results (total {}, sides swapped) -- \n=> {} win: {} ({}%)\n=> {} win: {} ({}%)\n=> tie: {} ({}%)",
+            n,
+            x_agent.name(),
+            score.wins,
+            x_stats,
+            o_agent.name(),
+            score.losses,
+            o_stats,
+            score.draws,
+            tie_stats
+        );
+    } else {
+        println!(
+            "-- fights results (total {}) -- \n=> X win ({}): {} ({}%)\n=> O win ({}): {} ({}%)\n=> tie: {} ({}%)",
+            n,
+            x_agent.name(),
+            score.wins,
+            x_stats,
+            o_agent.name(),
+            score.losses,
+            o_stats,
+            score.draws,
+            tie_stats
+        );
+    }
+    // Without openings, the advantage of playing first is part of the difference
+    print_elo(&score);
+    match session.time_control {
+        Some(time_control) => println!("-- thinking time (time control {}) --", time_control),
+        None => println!("-- thinking time --"),
+    }
+    println!("=> {}: {}", x_agent.name(), x_time);
+    println!("=> {}: {}", o_agent.name(), o_time);
+    result
+}
+
+/// Returns the number of threads used by default: one per CPU core.
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// A game to play: the agents, the position it starts from and its time control.
+#[derive(Clone)]
+pub struct Pairing {
+    pub x_agent: AgentSpec,
+    pub o_agent: AgentSpec,
+    pub start: Morpion,
+    pub time_control: Option<TimeControl>,
+}
+
+impl Pairing {
+    /// A game between two agents from the start, with unlimited time.
+    pub fn new(x_agent: AgentSpec, o_agent: AgentSpec) -> Self {
+        Self {
+            x_agent,
+            o_agent,
+            start: Morpion::new(),
+            time_control: None,
+        }
+    }
+}
+
+/// Plays one game per pairing, spread across `threads` threads.
+/// The game `i` uses the seed `seed + i`.
+/// `on_game` is called with the number of each game and the game, in the order of the pairings.
+/// Returns the games in the same order.
+pub fn fight_all(
+    pairings: &[Pairing],
+    seed: u64,
+    threads: usize,
+    mut on_game: impl FnMut(usize, &Game),
+) -> Vec<Game> {
+    let next = AtomicUsize::new(0);
+    let mut games: Vec<Option<Game>> = vec![None; pairings.len()];
+    thread::scope(|scope| {
+        let (sender, receiver) = channel();
+        for _ in 0..threads.clamp(1, pairings.len().max(1)) {
+            let (next, sender) = (&next, sender.clone());
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(pairing) = pairings.get(index) else {
+                    break;
+                };
+                let game = fight(pairing, seed.wrapping_add(index as u64));
+                if sender.send((index, game)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        // Games finish out of order, they are reported once all the previous ones are done
+        let mut reported = 0;
+        for (index, game) in receiver {
+            games[index] = Some(game);
+            while let Some(game) = games.get(reported).and_then(|game| game.as_ref()) {
+                on_game(reported, game);
+                reported += 1;
+            }
+        }
+    });
+    games.into_iter().map(Option::unwrap).collect()
 }
 
 /// Simulates a single AI vs AI fight.
-/// Plays a game of _Morpion_ between two AI players of specified levels and returns the game result.
-fn fight(x_level: AILevel, o_level: AILevel) -> PlayingState {
-    let mut morpion = Morpion::new();
+/// Plays a game of _Morpion_ between two agents from the position of the pairing,
+/// the random choices of the AI levels following `seed`.
+/// An agent that crashes, runs out of time or plays an illegal move loses the game.
+/// With a time control, an agent that thinks longer than allowed loses on time.
+pub fn fight(pairing: &Pairing, seed: u64) -> Game {
+    let (x_agent, o_agent) = (pairing.x_agent, pairing.o_agent);
+    random::seed(seed);
+    let mut morpion = pairing.start.clone();
+    let mut game = Game {
+        x_agent: x_agent.name(),
+        o_agent: o_agent.name(),
+        start: (morpion.to_notation() != Morpion::new().to_notation())
+            .then(|| morpion.to_notation()),
+        seed,
+        time_control: pairing.time_control,
+        moves: Vec::new(),
+        times: Vec::new(),
+        evaluations: Vec::new(),
+        result: PlayingState::Continue,
+        forfeit: None,
+    };
+    let forfeit = |mut game: Game, player: Player, reason: AgentError| {
+        game.result = Win(player.other());
+        game.forfeit = Some((player, reason));
+        game
+    };
+    let mut x = match x_agent.build() {
+        Ok(agent) => agent,
+        Err(e) => return forfeit(game, Player::X, e),
+    };
+    let mut o = match o_agent.build() {
+        Ok(agent) => agent,
+        Err(e) => return forfeit(game, Player::O, e),
+    };
+    // Remaining time of the clocks of X and O
+    let mut clocks = match pairing.time_control {
+        Some(TimeControl::Clock { base, .. }) => [base; 2],
+        _ => [Duration::MAX; 2],
+    };
     loop {
-        morpion = match morpion.player {
-            Player::X => morpion.ai_move(x_level),
-            Player::O => morpion.ai_move(o_level),
+        let (agent, clock) = match morpion.player {
+            Player::X => (x.as_mut(), &mut clocks[0]),
+            Player::O => (o.as_mut(), &mut clocks[1]),
+        };
+        let limit = match pairing.time_control {
+            _ if !agent.is_timed() => None,
+            None => None,
+            Some(TimeControl::PerMove(movetime)) => {
+                agent.set_movetime(without_overhead(movetime));
+                Some(movetime)
+            }
+            Some(TimeControl::Clock { increment, .. }) => {
+                agent.set_movetime(without_overhead(
+                    (*clock / MOVES_TO_GO + increment).min(*clock),
+                ));
+                Some(*clock)
+            }
         };
+        let start = Instant::now();
+        let result = timed_move(agent, &morpion, limit);
+        let time = start.elapsed();
+        let (ult_index, index) = match (result, limit) {
+            (Ok(Some(m)), None) => m,
+            (Ok(Some(m)), Some(limit)) if time <= limit => m,
+            (Ok(_), Some(limit)) => {
+                return forfeit(game, morpion.player, AgentError::Timeout(limit))
+            }
+            (Ok(None), None) => unreachable!("the fight can't be stopped without a time limit"),
+            (Err(e), _) => return forfeit(game, morpion.player, e),
+        };
+        if let (Some(TimeControl::Clock { increment, .. }), Some(_)) = (pairing.time_control, limit)
+        {
+            *clock = *clock - time + increment;
+        }
+        game.times.push(time);
+        game.moves.push((ult_index, index));
+        game.evaluations.push(agent.evaluation());
+        morpion.play_at(ult_index, index);
         if morpion.is_over() {
-            break morpion.state;
+            game.result = morpion.state;
+            break game;
         }
     }
 }
+
+/// Time an agent is told it has to think, out of the `time` it may use: [`MOVE_OVERHEAD`] less,
+/// or half of it if it is short.
+fn without_overhead(time: Duration) -> Duration {
+    time - MOVE_OVERHEAD.min(time / 2)
+}
+
+/// Asks an agent for a move, stopping it once `limit` is elapsed.
+/// Returns `Ok(None)` if the agent was stopped.
+fn timed_move(
+    agent: &mut dyn Agent,
+    morpion: &Morpion,
+    limit: Option<Duration>,
+) -> Result<Option<Move>, AgentError> {
+    let stop = AtomicBool::new(false);
+    let Some(limit) = limit else {
+        return checked_move(agent, morpion, &stop);
+    };
+    let (done, finished) = channel::<()>();
+    thread::scope(|scope| {
+        let stop = &stop;
+        scope.spawn(move || {
+            if finished.recv_timeout(limit) == Err(RecvTimeoutError::Timeout) {
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+        let result = checked_move(agent, morpion, stop);
+        let _ = done.send(());
+        result
+    })
+}
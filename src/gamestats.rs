@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::fight::Game;
+use crate::morpion::{format_move, CellState, Player};
+use crate::record::load_record;
+use crate::search::Move;
+use crate::session::load_session;
+use crate::stats::Score;
+
+/// Number of moves of the lengths grouped in a bar of the distribution.
+const LENGTH_BUCKET: usize = 10;
+/// Width of the longest bar of the distribution of the lengths.
+const BAR_WIDTH: usize = 40;
+
+/// Statistics on how a set of games went.
+#[derive(Clone, Debug, Default)]
+pub struct GameStats {
+    /// Number of games of each length, in moves.
+    lengths: BTreeMap<usize, usize>,
+    /// Results of the games, from the point of view of the player who moved first, `X` or `O` from an opening.
+    score: Score,
+    /// Results of the games played from the start, by first move, from `X`'s point of view.
+    first_moves: BTreeMap<Move, Score>,
+    /// Moves of `X` and `O` played with the choice of the board, after being sent to a finished one.
+    free_moves: [usize; 2],
+    /// Moves of `X` and `O`.
+    moves: [usize; 2],
+    /// Local boards won by `X` and `O` at the end of the games.
+    boards_won: [usize; 2],
+}
+
+/// Index of a player in the statistics of both players.
+fn side(player: Player) -> usize {
+    match player {
+        Player::X => 0,
+        Player::O => 1,
+    }
+}
+
+impl GameStats {
+    /// Records a game, replaying its moves.
+    pub fn add(&mut self, game: &Game) {
+        *self.lengths.entry(game.moves.len()).or_default() += 1;
+        let mut morpion = game.start_position();
+        self.score.add(&game.result, morpion.player);
+        if let (None, Some(first_move)) = (&game.start, game.moves.first()) {
+            self.first_moves
+                .entry(*first_move)
+                .or_default()
+                .add(&game.result, Player::X);
+        }
+        for (ply, &(ult_index, index)) in game.moves.iter().enumerate() {
+            let player = side(morpion.player);
+            // The first move of the game is free without being sent anywhere
+            if morpion.focused_big_cell.is_none() && (ply > 0 || game.start.is_some()) {
+                self.free_moves[player] += 1;
+            }
+            self.moves[player] += 1;
+            morpion.play_at(ult_index, index);
+        }
+        for state in morpion.board.states {
+            if let CellState::Occupied(player) = state {
+                self.boards_won[side(player)] += 1;
+            }
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.score.games()
+    }
+
+    /// Median length of the games, in moves.
+    fn median_length(&self) -> usize {
+        let mut remaining = self.games() / 2;
+        for (&length, &count) in &self.lengths {
+            if remaining < count {
+                return length;
+            }
+            remaining -= count;
+        }
+        0
+    }
+}
+
+impl std::fmt::Display for GameStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let games = self.games();
+        writeln!(f, "-- game statistics (total {}) --", games)?;
+        if games == 0 {
+            return Ok(());
+        }
+        let percent = |part: usize, total: usize| part as f64 / total.max(1) as f64 * 100.0;
+        let total_moves: usize = self.moves.iter().sum();
+        writeln!(
+            f,
+            "=> length: average {:.1} moves, median {}, shortest {}, longest {}",
+            total_moves as f64 / games as f64,
+            self.median_length(),
+            self.lengths.keys().next().unwrap_or(&0),
+            self.lengths.keys().last().unwrap_or(&0)
+        )?;
+        let mut buckets: BTreeMap<usize, usize> = BTreeMap::new();
+        for (length, count) in &self.lengths {
+            *buckets.entry(length / LENGTH_BUCKET).or_default() += count;
+        }
+        let highest = buckets.values().copied().max().unwrap_or(1);
+        for (bucket, count) in buckets {
+            writeln!(
+                f,
+                "   {:>3}-{:<3} {:>5} ({:>5.1}%) {}",
+                bucket * LENGTH_BUCKET,
+                (bucket + 1) * LENGTH_BUCKET - 1,
+                count,
+                percent(count, games),
+                "#".repeat((count * BAR_WIDTH).div_ceil(highest))
+            )?;
+        }
+        writeln!(
+            f,
+            "=> first player: {} (scores {:.1}%)",
+            self.score,
+            self.score.mean() * 100.0
+        )?;
+        if let Some(elo) = self.score.elo() {
+            writeln!(f, "=> first player advantage: {} Elo (95% confidence)", elo)?;
+        }
+        if !self.first_moves.is_empty() {
+            writeln!(f, "=> first moves of the games from the start:")?;
+            let mut first_moves: Vec<_> = self.first_moves.iter().collect();
+            first_moves.sort_by_key(|(_, score)| std::cmp::Reverse(score.games()));
+            for (first_move, score) in first_moves {
+                writeln!(
+                    f,
+                    "   {}: {:>5} games, X {} (X scores {:.1}%)",
+                    format_move(*first_move),
+                    score.games(),
+                    score,
+                    score.mean() * 100.0
+                )?;
+            }
+        }
+        writeln!(
+            f,
+            "=> free choice of the board: X {:.1}% of its moves, O {:.1}%",
+            percent(self.free_moves[0], self.moves[0]),
+            percent(self.free_moves[1], self.moves[1])
+        )?;
+        write!(
+            f,
+            "=> local boards won per game: X {:.2}, O {:.2}",
+            self.boards_won[0] as f64 / games as f64,
+            self.boards_won[1] as f64 / games as f64
+        )
+    }
+}
+
+/// Reads the games of a fight session file, or of a directory of game records (the `.txt` files, by name).
+/// The records that can't be read are reported and left out.
+pub fn load_games(path: &Path) -> Result<Vec<Game>, String> {
+    if !path.is_dir() {
+        return load_session(path).map(|(_, games)| games);
+    }
+    let mut paths: Vec<_> = fs::read_dir(path)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    paths.sort();
+    Ok(paths
+        .iter()
+        .filter_map(|path| match load_record(path) {
+            Ok(game) => Some(game),
+            Err(e) => {
+                println!("can't read {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Prints statistics on the games of a fight session file or of a directory of game records.
+pub fn launch_game_stats(path: &Path) -> Result<(), String> {
+    let games = load_games(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let mut stats = GameStats::default();
+    for game in &games {
+        stats.add(game);
+    }
+    println!("{}", stats);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::parse_record;
+
+    #[test]
+    fn first_player_of_an_opening() {
+        // O moves first from the opening and wins on time
+        let game = parse_record(
+            "x easy\no hard\nstart ........./........./........./........./x......../........./........./........./......... o 0\n\
+             seed 1\nresult O\nforfeit X no move within 100 ms\n04 1.0 -\n",
+        )
+        .unwrap();
+        let mut stats = GameStats::default();
+        stats.add(&game);
+        assert_eq!(stats.score.wins, 1);
+        assert!(stats.first_moves.is_empty());
+        assert_eq!(stats.moves, [0, 1]);
+    }
+}
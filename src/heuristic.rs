@@ -0,0 +1,264 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::ai::{
+    count_threats, dir, evaluate_winning_sequence, generate_moves, params, weighted_heuristic,
+    WINNING_WEIGHT,
+};
+use crate::morpion::{CellState, Morpion, Player, PlayingState};
+
+/// File from which the profile of the `custom` AI level is loaded when it exists.
+pub const PROFILE_FILE: &str = "profile.txt";
+
+static PROFILE: OnceLock<Profile> = OnceLock::new();
+
+/// A feature of a position that a [`Heuristic`] can weigh.
+/// Every term is computed from the point of view of the maximizing player.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Term {
+    /// Positional weights favoring the center (see [`crate::ai::center_heuristic`]).
+    Center,
+    /// Positional weights favoring the corners (see [`crate::ai::corner_heuristic`]).
+    Corner,
+    /// Winning sequences in the free big cells.
+    Sequences,
+    /// Winning sequences of the big board.
+    MetaSequences,
+    /// Number of won big cells.
+    WonBigCells,
+    /// Open two-in-a-rows in the free big cells.
+    LocalThreats,
+    /// Open two-in-a-rows of won big cells.
+    MetaThreats,
+    /// Number of moves of the player to move.
+    Mobility,
+    /// The player to move can play anywhere.
+    SentToFree,
+    /// The player to move is sent to a big cell they can win right away.
+    SentToThreat,
+}
+
+impl Term {
+    /// All the terms, in the order they are written in a profile.
+    pub const ALL: [Term; 10] = [
+        Term::Center,
+        Term::Corner,
+        Term::Sequences,
+        Term::MetaSequences,
+        Term::WonBigCells,
+        Term::LocalThreats,
+        Term::MetaThreats,
+        Term::Mobility,
+        Term::SentToFree,
+        Term::SentToThreat,
+    ];
+
+    /// Returns the name of the term, as written in a profile.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Term::Center => "center",
+            Term::Corner => "corner",
+            Term::Sequences => "sequences",
+            Term::MetaSequences => "meta_sequences",
+            Term::WonBigCells => "won_big_cells",
+            Term::LocalThreats => "local_threats",
+            Term::MetaThreats => "meta_threats",
+            Term::Mobility => "mobility",
+            Term::SentToFree => "sent_to_free",
+            Term::SentToThreat => "sent_to_threat",
+        }
+    }
+
+    /// Converts the name of a term into a [`Term`].
+    /// Returns `None` if the input string does not match any term.
+    pub fn from_str(s: &str) -> Option<Self> {
+        Term::ALL.into_iter().find(|term| term.name() == s)
+    }
+
+    /// Computes the term for a position where the game is not over.
+    fn evaluate(&self, node: &Morpion, maximizing_player: Player) -> isize {
+        let opponent = maximizing_player.other();
+        let states = &node.board.states;
+        let free_cells = || {
+            states
+                .iter()
+                .zip(node.board.cells.iter())
+                .filter(|(state, _)| **state == CellState::Free)
+                .map(|(_, cells)| cells)
+        };
+        match self {
+            Term::Center => weighted_heuristic(
+                node,
+                maximizing_player,
+                &params().weights_center,
+                params().big_cell_factor,
+            ),
+            Term::Corner => weighted_heuristic(
+                node,
+                maximizing_player,
+                &params().weights_corner,
+                params().big_cell_factor,
+            ),
+            Term::Sequences => free_cells()
+                .map(|cells| evaluate_winning_sequence(cells, maximizing_player))
+                .sum(),
+            Term::MetaSequences => evaluate_winning_sequence(states, maximizing_player),
+            Term::WonBigCells => states
+                .iter()
+                .map(|state| match state {
+                    CellState::Occupied(player) => dir(*player, maximizing_player),
+                    _ => 0,
+                })
+                .sum(),
+            Term::LocalThreats => free_cells()
+                .map(|cells| {
+                    count_threats(cells, maximizing_player) - count_threats(cells, opponent)
+                })
+                .sum(),
+            Term::MetaThreats => {
+                count_threats(states, maximizing_player) - count_threats(states, opponent)
+            }
+            Term::Mobility => {
+                dir(node.player, maximizing_player) * generate_moves(node).len() as isize
+            }
+            Term::SentToFree => match node.focused_big_cell {
+                None => dir(node.player, maximizing_player),
+                Some(_) => 0,
+            },
+            Term::SentToThreat => match node.focused_big_cell {
+                Some(big_cell_index)
+                    if count_threats(&node.board.cells[big_cell_index], node.player) > 0 =>
+                {
+                    dir(node.player, maximizing_player)
+                }
+                _ => 0,
+            },
+        }
+    }
+}
+
+/// A heuristic made of weighted [`Term`]s, built with [`Heuristic::with`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Heuristic {
+    terms: Vec<(Term, isize)>,
+}
+
+impl Heuristic {
+    /// Creates a heuristic without any term, scoring only the end of the game.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a term with the given weight (replacing its weight if the term is already there).
+    pub fn with(mut self, term: Term, weight: isize) -> Self {
+        match self.terms.iter_mut().find(|(t, _)| *t == term) {
+            Some((_, w)) => *w = weight,
+            None => self.terms.push((term, weight)),
+        }
+        self
+    }
+
+    /// Evaluates a game state: the weighted sum of the terms, or [`WINNING_WEIGHT`] if the game is won.
+    pub fn evaluate(&self, node: &Morpion, maximizing_player: Player) -> isize {
+        match node.state {
+            PlayingState::Continue => self
+                .terms
+                .iter()
+                .filter(|(_, weight)| *weight != 0)
+                .map(|(term, weight)| weight * term.evaluate(node, maximizing_player))
+                .sum(),
+            PlayingState::Win(player) => dir(player, maximizing_player) * WINNING_WEIGHT,
+            PlayingState::Tie => 0,
+        }
+    }
+}
+
+/// Search settings of the `custom` AI level: a composed heuristic and a search depth.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub depth: isize,
+    pub heuristic: Heuristic,
+}
+
+impl Default for Profile {
+    /// Close to the `hard` level.
+    fn default() -> Self {
+        Self {
+            depth: 6,
+            heuristic: Heuristic::new()
+                .with(Term::Sequences, 1)
+                .with(Term::MetaSequences, 2)
+                .with(Term::WonBigCells, 5)
+                .with(Term::SentToFree, 2),
+        }
+    }
+}
+
+impl Profile {
+    /// Parses a profile from its text representation: a `depth = N` line
+    /// and one `term = weight` line per term used, lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut profile = Profile {
+            depth: Profile::default().depth,
+            heuristic: Heuristic::new(),
+        };
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("invalid line: {}", line))?;
+            let (name, value) = (name.trim(), value.trim());
+            let value = value
+                .parse::<isize>()
+                .map_err(|e| format!("invalid value for {}: {}", name, e))?;
+            if name == "depth" {
+                if value < 1 {
+                    return Err(format!("depth must be at least 1, got {}", value));
+                }
+                profile.depth = value;
+            } else {
+                let term = Term::from_str(name).ok_or_else(|| format!("unknown term: {}", name))?;
+                profile.heuristic = profile.heuristic.with(term, value);
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Loads a profile from a file, in the format read by [`Profile::parse`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "depth = {}", self.depth)?;
+        for (term, weight) in &self.heuristic.terms {
+            writeln!(f, "{} = {}", term.name(), weight)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the profile used by the `custom` AI level.
+/// It is loaded from [`PROFILE_FILE`] the first time, falling back to the default profile.
+pub fn profile() -> &'static Profile {
+    PROFILE.get_or_init(|| match Profile::load(Path::new(PROFILE_FILE)) {
+        Ok(profile) => profile,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Profile::default(),
+        Err(e) => {
+            println!("can't load {}: {}, using default profile", PROFILE_FILE, e);
+            Profile::default()
+        }
+    })
+}
+
+/// Heuristic of the `custom` AI level, composed from the loaded profile.
+pub fn custom_heuristic(node: &Morpion, maximizing_player: Player) -> isize {
+    profile().heuristic.evaluate(node, maximizing_player)
+}
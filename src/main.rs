@@ -1,22 +1,51 @@
 use ggegui::egui::{self, Button, Label};
 use ggez::event::{self, EventHandler, MouseButton};
 use ggez::graphics::{self, Color, DrawParam, Drawable};
-use ggez::{Context, GameError, GameResult};
+use ggez::{Context, GameResult};
 
+use std::process::ExitCode;
 use std::{env, path};
 
+mod agent;
 mod ai;
+mod analysis;
 mod assets;
+mod book;
+mod cli;
+mod codingame;
 mod constants;
+mod engine;
+mod external;
+mod heuristic;
 mod menu;
+mod model;
 mod morpion;
+mod opening;
+mod perft;
+mod random;
 mod fight;
+mod gamestats;
+mod rating;
+mod record;
+mod replay;
+mod report;
+mod search;
+mod session;
+mod skill;
+mod solver;
+mod stats;
+mod tournament;
+mod tuning;
+mod worker;
 
 use ai::AILevel;
 use constants::{BIG_CELL_SIZE, BORDER_PADDING, CELL_PADDING, CELL_SIZE, SCREEN_SIZE};
 use menu::Menu;
 use morpion::{CellState, Morpion, MorpionScene, Player, PlayingState};
-use crate::fight::launch_fights;
+use crate::agent::{engines, AgentSpec};
+use crate::cli::{CliError, Launch};
+use crate::replay::Replay;
+use crate::skill::{MAX_SKILL, MIN_SKILL};
 
 #[derive(PartialEq, Eq, Clone)]
 enum GameState {
@@ -28,8 +57,10 @@ enum GameState {
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum GameMode {
     PvP,
-    PvAI(AILevel),
-    AIvAI(AILevel, AILevel),
+    PvAI(AgentSpec),
+    AIvAI(AgentSpec, AgentSpec),
+    /// A recorded game, see [`MorpionScene::replay`].
+    Replay,
 }
 
 struct Game {
@@ -39,10 +70,17 @@ struct Game {
 }
 
 impl Game {
-    fn new(ctx: &mut Context) -> GameResult<Self> {
+    /// Starts on the menu, or on the replayed game if any.
+    fn new(ctx: &mut Context, replay: Option<Replay>) -> GameResult<Self> {
+        let mut morpion_scene = MorpionScene::new(ctx)?;
+        let state = match replay {
+            Some(_) => GameState::Playing(GameMode::Replay),
+            None => GameState::StartMenu,
+        };
+        morpion_scene.replay = replay;
         Ok(Self {
-            morpion_scene: MorpionScene::new(ctx)?,
-            state: GameState::StartMenu,
+            morpion_scene,
+            state,
             menu: Menu::new(ctx),
         })
     }
@@ -69,6 +107,14 @@ impl EventHandler for Game {
                         if ui.add_sized([150.0, 50.0], Button::new("AIvAI")).clicked() {
                             self.state = GameState::SelectAIMenu(true);
                         }
+                        ui.checkbox(
+                            &mut self.morpion_scene.pondering,
+                            "AI thinks on your time",
+                        );
+                        ui.checkbox(
+                            &mut self.morpion_scene.show_evaluation,
+                            "Show the evaluation bar",
+                        );
                     });
                 });
                 self.menu.gui.update(ctx);
@@ -81,14 +127,26 @@ impl EventHandler for Game {
                         ui.add_sized([150.0, 50.0], Label::new("Ultimate Morpion"));
 
                         if !multi_ai {
-                            if ui.add_sized([150.0, 50.0], Button::new("Easy")).clicked() {
-                                self.state = GameState::Playing(GameMode::PvAI(AILevel::Easy));
-                            }
-                            if ui.add_sized([150.0, 50.0], Button::new("Medium")).clicked() {
-                                self.state = GameState::Playing(GameMode::PvAI(AILevel::Medium));
+                            ui.add(
+                                egui::Slider::new(&mut self.menu.skill, MIN_SKILL..=MAX_SKILL)
+                                    .text("Skill"),
+                            );
+                            let skill = AgentSpec::Level(AILevel::Skill(self.menu.skill));
+                            let label = self.menu.difficulty_label(skill);
+                            if ui.add_sized([150.0, 50.0], Button::new(label)).clicked() {
+                                self.state = GameState::Playing(GameMode::PvAI(skill));
                             }
-                            if ui.add_sized([150.0, 50.0], Button::new("Hard")).clicked() {
-                                self.state = GameState::Playing(GameMode::PvAI(AILevel::Hard));
+                            // The skill slider replaces the easy, medium and hard levels and the rated skills
+                            let fixed =
+                                [AILevel::Easy, AILevel::Medium, AILevel::Hard].map(AgentSpec::Level);
+                            for agent in AgentSpec::all().into_iter().filter(|agent| {
+                                !fixed.contains(agent)
+                                    && !matches!(agent, AgentSpec::Level(AILevel::Skill(_)))
+                            }) {
+                                let label = self.menu.difficulty_label(agent);
+                                if ui.add_sized([150.0, 50.0], Button::new(label)).clicked() {
+                                    self.state = GameState::Playing(GameMode::PvAI(agent));
+                                }
                             }
                         } else {
                             ui.horizontal(|ui| {
@@ -98,8 +156,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Easy,
-                                            AILevel::Medium,
+                                            AgentSpec::Level(AILevel::Easy),
+                                            AgentSpec::Level(AILevel::Medium),
                                         ));
                                     }
                                     if ui
@@ -107,8 +165,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Easy,
-                                            AILevel::Hard,
+                                            AgentSpec::Level(AILevel::Easy),
+                                            AgentSpec::Level(AILevel::Hard),
                                         ));
                                     }
                                     if ui
@@ -116,8 +174,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Medium,
-                                            AILevel::Hard,
+                                            AgentSpec::Level(AILevel::Medium),
+                                            AgentSpec::Level(AILevel::Hard),
                                         ));
                                     }
                                 });
@@ -127,8 +185,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Medium,
-                                            AILevel::Easy,
+                                            AgentSpec::Level(AILevel::Medium),
+                                            AgentSpec::Level(AILevel::Easy),
                                         ));
                                     }
                                     if ui
@@ -136,8 +194,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Hard,
-                                            AILevel::Easy,
+                                            AgentSpec::Level(AILevel::Hard),
+                                            AgentSpec::Level(AILevel::Easy),
                                         ));
                                     }
                                     if ui
@@ -145,8 +203,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Hard,
-                                            AILevel::Medium,
+                                            AgentSpec::Level(AILevel::Hard),
+                                            AgentSpec::Level(AILevel::Medium),
                                         ));
                                     }
                                 });
@@ -156,8 +214,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Easy,
-                                            AILevel::Easy,
+                                            AgentSpec::Level(AILevel::Easy),
+                                            AgentSpec::Level(AILevel::Easy),
                                         ));
                                     }
                                     if ui
@@ -165,8 +223,8 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Medium,
-                                            AILevel::Medium,
+                                            AgentSpec::Level(AILevel::Medium),
+                                            AgentSpec::Level(AILevel::Medium),
                                         ));
                                     }
                                     if ui
@@ -174,12 +232,24 @@ impl EventHandler for Game {
                                         .clicked()
                                     {
                                         self.state = GameState::Playing(GameMode::AIvAI(
-                                            AILevel::Hard,
-                                            AILevel::Hard,
+                                            AgentSpec::Level(AILevel::Hard),
+                                            AgentSpec::Level(AILevel::Hard),
                                         ));
                                     }
                                 });
                             });
+                            // External engines play against the hardest level
+                            let hard = AgentSpec::Level(AILevel::Hard);
+                            for engine in (0..engines().len()).map(AgentSpec::Engine) {
+                                ui.horizontal(|ui| {
+                                    for (x, o) in [(engine, hard), (hard, engine)] {
+                                        let label = format!("{} vs {}", x.name(), o.name());
+                                        if ui.add_sized([150.0, 50.0], Button::new(label)).clicked() {
+                                            self.state = GameState::Playing(GameMode::AIvAI(x, o));
+                                        }
+                                    }
+                                });
+                            }
                         }
 
                         if ui.add_sized([100.0, 30.0], Button::new("Back")).clicked() {
@@ -211,10 +281,19 @@ impl EventHandler for Game {
     ) -> GameResult {
         if let Some((ult_index, index)) = ids_from_coord(x, y) {
             self.morpion_scene.clicked = Some((ult_index, index));
+        } else if matches!(self.state, GameState::Playing(_))
+            && self.morpion_scene.hint_button_contains(x, y)
+        {
+            self.morpion_scene.hint_requested = true;
         }
         Ok(())
     }
 
+    fn quit_event(&mut self, _ctx: &mut Context) -> GameResult<bool> {
+        self.morpion_scene.shutdown();
+        Ok(false)
+    }
+
     fn mouse_button_up_event(
         &mut self,
         _ctx: &mut Context,
@@ -264,42 +343,43 @@ fn coord_from_ids(ult_index: usize, index: usize) -> (f32, f32) {
     )
 }
 
-fn main() -> GameResult {
-    let args: Vec<String> = env::args().collect();
-    let args_len = args.len();
-    if args_len == 5 {
-        let arg_type = &args[1];
-        let ai_level_x = &args[2];
-        let ai_level_o = &args[3];
-        let fight_number = &args[4];
-
-        match arg_type.as_str() {
-            "-f" => {
-                let x_level = AILevel::from_str(ai_level_x).unwrap_or(AILevel::Easy);
-                let o_level = AILevel::from_str(ai_level_o).unwrap_or(AILevel::Easy);
-                launch_fights(x_level, o_level, fight_number.parse::<usize>().unwrap_or(50));
-            },
-            _ => { panic!("unknown argument: {}", arg_type); }
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match cli::run(&args) {
+        Ok(Launch::Done) => ExitCode::SUCCESS,
+        Ok(Launch::Window(replay)) => {
+            if let Err(e) = launch_window(replay.map(|replay| *replay)) {
+                eprintln!("can't launch window: {}", e);
+            }
+            ExitCode::FAILURE
+        }
+        Err(CliError::Usage(message)) => {
+            eprintln!("{}", message);
+            ExitCode::from(2)
         }
+        Err(CliError::Failure(message)) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
 
-        return Ok(());
-    } else if args_len == 1 {
-        let resource_dir = if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
-            let mut path = path::PathBuf::from(manifest_dir);
-            path.push("resources");
-            path
-        } else {
-            path::PathBuf::from("./resources")
-        };
+/// Opens the game window, on the start menu or on a replayed game. Only returns if it can't be opened.
+fn launch_window(replay: Option<Replay>) -> GameResult {
+    let resource_dir = if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("resources");
+        path
+    } else {
+        path::PathBuf::from("./resources")
+    };
 
-        let (mut ctx, events_loop) = ggez::ContextBuilder::new("ultimate-morpion", "lilBchii")
-            .add_resource_path(resource_dir)
-            .window_setup(ggez::conf::WindowSetup::default().title("ultimate-morpion"))
-            .window_mode(ggez::conf::WindowMode::default().dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1))
-            .build()?;
+    let (mut ctx, events_loop) = ggez::ContextBuilder::new("ultimate-morpion", "lilBchii")
+        .add_resource_path(resource_dir)
+        .window_setup(ggez::conf::WindowSetup::default().title("ultimate-morpion"))
+        .window_mode(ggez::conf::WindowMode::default().dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1))
+        .build()?;
 
-        let state = Game::new(&mut ctx)?;
-        event::run(ctx, events_loop, state)
-    }
-    Err(GameError::CustomError("can't launch window: invalid arguments".to_string()))
+    let state = Game::new(&mut ctx, replay)?;
+    event::run(ctx, events_loop, state)
 }
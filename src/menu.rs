@@ -4,16 +4,66 @@ use ggez::{
     Context,
 };
 use glam::vec2;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
 
+use crate::agent::AgentSpec;
+use crate::ai::AILevel;
 use crate::constants::BORDER_PADDING;
+use crate::rating::{load_ratings, Rating, RATINGS_FILE};
+use crate::skill::RATED_SKILLS;
+
+/// Skill selected when the game starts.
+const DEFAULT_SKILL: u8 = 10;
 
 pub struct Menu {
     pub gui: Gui,
+    /// Ratings of the agents, read from [`RATINGS_FILE`] if it exists.
+    ratings: HashMap<AgentSpec, Rating>,
+    /// Skill chosen with the slider of the PvAI menu.
+    pub skill: u8,
 }
 
 impl Menu {
     pub fn new(ctx: &mut Context) -> Self {
-        Self { gui: Gui::new(ctx) }
+        let ratings = match load_ratings(Path::new(RATINGS_FILE)) {
+            Ok(ratings) => ratings,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                println!("can't load {}: {}", RATINGS_FILE, e);
+                HashMap::new()
+            }
+        };
+        Self {
+            gui: Gui::new(ctx),
+            ratings,
+            skill: DEFAULT_SKILL,
+        }
+    }
+
+    /// Returns the label of the button choosing the given opponent, with its rating if known.
+    /// A skill which isn't rated shows the rating of the nearest rated skill, as an estimate.
+    pub fn difficulty_label(&self, agent: AgentSpec) -> String {
+        let (name, rated) = match agent {
+            AgentSpec::Level(AILevel::Skill(skill)) => {
+                let nearest = RATED_SKILLS
+                    .into_iter()
+                    .min_by_key(|rated| rated.abs_diff(skill))
+                    .unwrap();
+                (
+                    format!("Skill {}", skill),
+                    AgentSpec::Level(AILevel::Skill(nearest)),
+                )
+            }
+            AgentSpec::Level(level) => (format!("{:?}", level), agent),
+            AgentSpec::Engine(_) | AgentSpec::Profile(_) => (agent.name(), agent),
+        };
+        match self.ratings.get(&rated) {
+            Some(rating) if rated == agent => format!("{} ({})", name, rating),
+            Some(rating) => format!("{} (~{})", name, rating),
+            None => name,
+        }
     }
 }
 
@@ -0,0 +1,336 @@
+use rand::Rng;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::ai::{evaluate_winning_sequence, AILevel, WINNING_WEIGHT};
+use crate::morpion::{CellState, Morpion, Player, PlayingState};
+use crate::tuning::{self_play, Sample};
+
+/// File from which the model used by [`learned_heuristic`] is loaded.
+pub const MODEL_FILE: &str = "model.txt";
+/// Number of features extracted from a position by [`features`].
+pub const FEATURES: usize = 81 * 2 + 9 * 3 + 4;
+/// Factor converting the output of the model into a heuristic score.
+const SCORE_SCALE: f32 = 100.0;
+/// Number of passes over the samples during training.
+const EPOCHS: usize = 30;
+/// Learning rate of the stochastic gradient descent.
+const LEARNING_RATE: f32 = 0.01;
+
+static MODEL: OnceLock<Option<Model>> = OnceLock::new();
+
+/// A small CPU-only evaluation model: a linear model if `hidden` is `0`,
+/// otherwise a multilayer perceptron with one hidden layer of `hidden` ReLU units.
+/// The output is the logit of the probability that the player the features were extracted for wins.
+#[derive(Clone, Debug)]
+pub struct Model {
+    hidden: usize,
+    /// Weights of the hidden layer (`hidden` rows of [`FEATURES`] values), unused if linear.
+    w1: Vec<f32>,
+    b1: Vec<f32>,
+    /// Weights of the output layer (`hidden` values, or [`FEATURES`] values if linear).
+    w2: Vec<f32>,
+    b2: f32,
+}
+
+/// Extracts the features of a position from the point of view of `player`:
+/// cells and big cells owned by each side, winning sequences, free choice and side to move.
+pub fn features(node: &Morpion, player: Player) -> Vec<f32> {
+    let mut features = vec![0.0; FEATURES];
+    let owner_offset = |cell: CellState| match cell {
+        CellState::Occupied(p) if p == player => Some(0),
+        CellState::Occupied(_) => Some(1),
+        CellState::Tie => Some(2),
+        CellState::Free => None,
+    };
+    for (big_cell_index, big_cell) in node.board.cells.iter().enumerate() {
+        for (lil_cell_index, cell) in big_cell.iter().enumerate() {
+            if let Some(offset @ 0..=1) = owner_offset(*cell) {
+                features[offset * 81 + big_cell_index * 9 + lil_cell_index] = 1.0;
+            }
+        }
+    }
+    for (big_cell_index, state) in node.board.states.iter().enumerate() {
+        if let Some(offset) = owner_offset(*state) {
+            features[162 + offset * 9 + big_cell_index] = 1.0;
+        }
+    }
+    features[189] = evaluate_winning_sequence(&node.board.states, player) as f32;
+    features[190] = node
+        .board
+        .states
+        .iter()
+        .zip(node.board.cells.iter())
+        .filter(|(state, _)| **state == CellState::Free)
+        .map(|(_, cells)| evaluate_winning_sequence(cells, player) as f32)
+        .sum::<f32>()
+        / 9.0;
+    let to_move = if node.player == player { 1.0 } else { -1.0 };
+    if node.focused_big_cell.is_none() {
+        features[191] = to_move;
+    }
+    features[192] = to_move;
+    features
+}
+
+impl Model {
+    /// Creates a model with small random weights.
+    pub fn new(hidden: usize) -> Self {
+        let mut rng = rand::rng();
+        let mut random =
+            |len: usize| -> Vec<f32> { (0..len).map(|_| rng.random_range(-0.1..0.1)).collect() };
+        if hidden == 0 {
+            Self {
+                hidden,
+                w1: Vec::new(),
+                b1: Vec::new(),
+                w2: vec![0.0; FEATURES],
+                b2: 0.0,
+            }
+        } else {
+            Self {
+                hidden,
+                w1: random(hidden * FEATURES),
+                b1: vec![0.0; hidden],
+                w2: random(hidden),
+                b2: 0.0,
+            }
+        }
+    }
+
+    /// Computes the activations of the hidden layer (the features themselves if linear).
+    fn hidden_layer(&self, features: &[f32]) -> Vec<f32> {
+        if self.hidden == 0 {
+            return features.to_vec();
+        }
+        (0..self.hidden)
+            .map(|h| {
+                let row = &self.w1[h * FEATURES..(h + 1) * FEATURES];
+                let sum: f32 = row.iter().zip(features).map(|(w, x)| w * x).sum();
+                (sum + self.b1[h]).max(0.0)
+            })
+            .collect()
+    }
+
+    /// Returns the logit predicted for the given features.
+    pub fn predict(&self, features: &[f32]) -> f32 {
+        let hidden = self.hidden_layer(features);
+        hidden.iter().zip(&self.w2).map(|(w, x)| w * x).sum::<f32>() + self.b2
+    }
+
+    /// Does one step of stochastic gradient descent on the logistic loss for a single sample.
+    fn train_step(&mut self, features: &[f32], result: f32, learning_rate: f32) {
+        let hidden = self.hidden_layer(features);
+        let logit = hidden.iter().zip(&self.w2).map(|(w, x)| w * x).sum::<f32>() + self.b2;
+        let error = 1.0 / (1.0 + (-logit).exp()) - result;
+        if self.hidden > 0 {
+            for (h, activation) in hidden.iter().enumerate() {
+                if *activation <= 0.0 {
+                    continue;
+                }
+                let gradient = error * self.w2[h];
+                let row = &mut self.w1[h * FEATURES..(h + 1) * FEATURES];
+                for (w, x) in row.iter_mut().zip(features) {
+                    *w -= learning_rate * gradient * x;
+                }
+                self.b1[h] -= learning_rate * gradient;
+            }
+        }
+        for (w, x) in self.w2.iter_mut().zip(&hidden) {
+            *w -= learning_rate * error * x;
+        }
+        self.b2 -= learning_rate * error;
+    }
+
+    /// Fits the model on self-play samples, from both players' points of view.
+    pub fn train(&mut self, samples: &[Sample]) {
+        let mut rng = rand::rng();
+        let mut data = training_data(samples);
+        for epoch in 0..EPOCHS {
+            for i in (1..data.len()).rev() {
+                data.swap(i, rng.random_range(0..=i));
+            }
+            for (features, result) in &data {
+                self.train_step(features, *result, LEARNING_RATE);
+            }
+            println!("epoch {}: loss {:.6}", epoch + 1, self.loss(&data));
+        }
+    }
+
+    /// Computes the mean logistic loss of the model on features labelled with results.
+    fn loss(&self, data: &[(Vec<f32>, f32)]) -> f32 {
+        let total: f32 = data
+            .iter()
+            .map(|(features, result)| {
+                let p = 1.0 / (1.0 + (-self.predict(features)).exp());
+                -(result * p.max(1e-6).ln() + (1.0 - result) * (1.0 - p).max(1e-6).ln())
+            })
+            .sum();
+        total / data.len() as f32
+    }
+
+    /// Parses a model from its text representation (see [`Model::save`]).
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or("empty model file")?
+            .split_whitespace()
+            .collect();
+        let (inputs, hidden) = match header[..] {
+            ["morpion-model", inputs, hidden] => (
+                inputs.parse::<usize>().map_err(|e| e.to_string())?,
+                hidden.parse::<usize>().map_err(|e| e.to_string())?,
+            ),
+            _ => return Err("invalid model header".to_string()),
+        };
+        if inputs != FEATURES {
+            return Err(format!(
+                "model expects {} features, not {}",
+                inputs, FEATURES
+            ));
+        }
+        let mut next_values = |len: usize| -> Result<Vec<f32>, String> {
+            let values = lines
+                .next()
+                .unwrap_or("")
+                .split_whitespace()
+                .map(|v| v.parse::<f32>().map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()?;
+            if values.len() != len {
+                return Err(format!("expected {} values, got {}", len, values.len()));
+            }
+            Ok(values)
+        };
+        let (w1, b1) = if hidden == 0 {
+            (Vec::new(), Vec::new())
+        } else {
+            (next_values(hidden * FEATURES)?, next_values(hidden)?)
+        };
+        let w2 = next_values(if hidden == 0 { FEATURES } else { hidden })?;
+        let b2 = next_values(1)?[0];
+        Ok(Self {
+            hidden,
+            w1,
+            b1,
+            w2,
+            b2,
+        })
+    }
+
+    /// Loads a model from a file written by [`Model::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the model to a file: a header line followed by one line per weight vector.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let line = |values: &[f32]| -> String {
+            values
+                .iter()
+                .map(f32::to_string)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let mut text = format!("morpion-model {} {}\n", FEATURES, self.hidden);
+        if self.hidden > 0 {
+            text += &format!("{}\n{}\n", line(&self.w1), line(&self.b1));
+        }
+        text += &format!("{}\n{}\n", line(&self.w2), self.b2);
+        fs::write(path, text)
+    }
+}
+
+/// Labels the features of the samples from both players' points of view with their results.
+fn training_data(samples: &[Sample]) -> Vec<(Vec<f32>, f32)> {
+    samples
+        .iter()
+        .flat_map(|sample| {
+            let result = sample.result as f32;
+            [
+                (features(&sample.morpion, Player::X), result),
+                (features(&sample.morpion, Player::O), 1.0 - result),
+            ]
+        })
+        .collect()
+}
+
+/// Returns the model used by [`learned_heuristic`], loaded from [`MODEL_FILE`] the first time.
+/// Returns `None` if there is no model to load: the learned level is then not available.
+pub fn model() -> Option<&'static Model> {
+    MODEL
+        .get_or_init(|| match Model::load(Path::new(MODEL_FILE)) {
+            Ok(model) => Some(model),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                println!("can't load {}: {}", MODEL_FILE, e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+/// Heuristic function evaluating the position with the learned [`Model`].
+/// Only used by the learned level, which is available only if there is a model.
+pub fn learned_heuristic(node: &Morpion, maximizing_player: Player) -> isize {
+    match node.state {
+        PlayingState::Continue => {
+            let model = model().expect("the learned level needs a model");
+            (model.predict(&features(node, maximizing_player)) * SCORE_SCALE) as isize
+        }
+        PlayingState::Win(player) if player == maximizing_player => WINNING_WEIGHT,
+        PlayingState::Win(_) => -WINNING_WEIGHT,
+        PlayingState::Tie => 0,
+    }
+}
+
+/// Launches a training session.
+/// Generates `games` self-play games, fits a model with `hidden` hidden units and writes it to `output`.
+pub fn launch_training(hidden: usize, games: usize, output: &Path) -> Result<(), String> {
+    let samples = self_play(games, AILevel::Easy);
+    println!("-- {} positions collected --", samples.len());
+    let mut model = Model::new(hidden);
+    model.train(&samples);
+    model
+        .save(output)
+        .map_err(|e| format!("can't write {}: {}", output.display(), e))?;
+    println!("-- model written to {} --", output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_have_the_expected_length() {
+        let mut morpion = Morpion::new();
+        assert_eq!(features(&morpion, Player::X).len(), FEATURES);
+        morpion.play_at(4, 4);
+        morpion.play_at(4, 0);
+        assert_eq!(features(&morpion, Player::O).len(), FEATURES);
+    }
+
+    #[test]
+    fn training_lowers_the_loss() {
+        let mut morpion = Morpion::new();
+        let mut samples = Vec::new();
+        for (ult_index, index) in [(4, 4), (4, 0), (0, 4), (4, 8), (8, 4), (4, 2)] {
+            morpion.play_at(ult_index, index);
+            samples.push(Sample {
+                morpion: morpion.clone(),
+                result: 1.0,
+            });
+        }
+        let data = training_data(&samples);
+        for hidden in [0, 4] {
+            let mut model = Model::new(hidden);
+            let initial = model.loss(&data);
+            model.train(&samples);
+            assert!(model.loss(&data) < initial);
+        }
+    }
+}
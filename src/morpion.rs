@@ -1,15 +1,21 @@
-use ggez::graphics::{Color, DrawParam, Drawable, Rect, Text};
+use ggez::graphics::{Color, DrawParam, Drawable, Quad, Rect, Text};
 use ggez::input::keyboard::KeyCode;
 use ggez::{Context, GameResult};
 use glam::Vec2;
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::thread;
-use std::thread::JoinHandle;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::agent::AgentSpec;
 use crate::ai::{
-    alpha_beta, center_heuristic, corner_heuristic, everywhere_heuristic, generate_children, noise,
-    AILevel,
+    alpha_beta, center_heuristic, corner_heuristic, everywhere_heuristic, generate_moves, noise,
+    threat_heuristic, AILevel, Aids, Evaluation, HeuristicFn, ScoreTable,
 };
+use crate::book::book_move;
+use crate::heuristic::{custom_heuristic, profile};
+use crate::model::learned_heuristic;
+use crate::replay::Replay;
+use crate::skill::{skill_depth, skill_move};
+use crate::solver::{endgame_solution, Solution};
+use crate::worker::AIWorker;
 use crate::{assets::Assets, coord_from_ids};
 use crate::{constants::*, GameMode, GameState};
 
@@ -28,6 +34,16 @@ impl Player {
             Player::O => Player::X,
         }
     }
+
+    /// Converts a string into a [`Player`].
+    /// Returns `None` if the input string does not match any player.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "x" | "X" => Some(Player::X),
+            "o" | "O" => Some(Player::O),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for Player {
@@ -106,6 +122,24 @@ pub fn is_won_by(states: &[CellState; 9], player: Player) -> bool {
         || (states[2] == player && states[4] == player && states[6] == player)
 }
 
+/// Writes a move `(ult_index, index)` as two digits, e.g. `40` for the top left cell of the center big cell.
+pub fn format_move((ult_index, index): (usize, usize)) -> String {
+    format!("{}{}", ult_index, index)
+}
+
+/// Reads a move written by [`format_move`].
+pub fn parse_move(s: &str) -> Result<(usize, usize), String> {
+    let digits: Vec<usize> = s
+        .chars()
+        .map(|c| c.to_digit(10).map(|d| d as usize))
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("invalid move: {}", s))?;
+    match digits[..] {
+        [ult_index, index] if ult_index < 9 && index < 9 => Ok((ult_index, index)),
+        _ => Err(format!("invalid move: {}", s)),
+    }
+}
+
 /// Represents the current state of the game.
 #[derive(Debug, PartialEq, Clone)]
 pub enum PlayingState {
@@ -114,6 +148,19 @@ pub enum PlayingState {
     Continue,
 }
 
+/// Returns the depth and the heuristic of the search of an AI level.
+fn search_params(ai_level: AILevel) -> (isize, &'static HeuristicFn<'static>) {
+    match ai_level {
+        AILevel::Easy => (5, &corner_heuristic),
+        AILevel::Medium => (6, &center_heuristic),
+        AILevel::Hard => (6, &everywhere_heuristic),
+        AILevel::Threat => (6, &threat_heuristic),
+        AILevel::Custom => (profile().depth, &custom_heuristic),
+        AILevel::Learned => (5, &learned_heuristic),
+        AILevel::Skill(skill) => (skill_depth(skill), &everywhere_heuristic),
+    }
+}
+
 /// Represents the game logic and state management for the game (_Morpion_).
 #[derive(Clone)]
 pub struct Morpion {
@@ -280,45 +327,177 @@ impl Morpion {
     }
 
     /// Computes the next AI move based on the given AI level.
-    /// Uses the _Alpha-Beta pruning algorithm_ with different heuristics.
+    /// Uses the _Alpha-Beta pruning algorithm_ with different heuristics,
+    /// or the exact solver near the end of the game unless it proves a loss.
     pub fn ai_move(&self, ai_level: AILevel) -> Self {
-        let children = generate_children(self);
-        let mut best_move_index = 0;
+        self.ai_move_until(ai_level, &AtomicBool::new(false))
+            .expect("search can't be stopped")
+    }
+
+    /// Same as [`Morpion::ai_move`], but the search can be aborted by setting `stop`.
+    /// Returns `None` if the search was aborted.
+    pub fn ai_move_until(&self, ai_level: AILevel, stop: &AtomicBool) -> Option<Self> {
+        let (ult_index, index) = self.ai_best_move(ai_level, stop)?;
+        let mut child = self.clone();
+        child.play_at(ult_index, index);
+        Some(child)
+    }
+
+    /// Returns the move `(ult_index, index)` chosen by the AI, see [`Morpion::ai_move`].
+    /// Returns `None` if the search was aborted.
+    pub fn ai_best_move(&self, ai_level: AILevel, stop: &AtomicBool) -> Option<(usize, usize)> {
+        self.ai_choice(ai_level, &mut ScoreTable::new(), stop)
+            .map(|(best_move, _)| best_move)
+    }
+
+    /// Same as [`Morpion::ai_best_move`], with the evaluation of the position that led to the move
+    /// when the AI knows it. The scores found are kept in `table`, only used by this level.
+    pub fn ai_choice(
+        &self,
+        ai_level: AILevel,
+        table: &mut ScoreTable,
+        stop: &AtomicBool,
+    ) -> Option<((usize, usize), Option<Evaluation>)> {
+        if let AILevel::Skill(skill) = ai_level {
+            return skill_move(self, skill, table, stop).map(|best_move| (best_move, None));
+        }
+        let (depth, heuristic) = search_params(ai_level);
+        self.choice_with(depth, heuristic, ai_level.aids(), table, stop)
+    }
+
+    /// Returns the move `(ult_index, index)` of the solver if it proves the position is not lost,
+    /// or else the move of the opening book, each one only if the AI uses it, with the evaluation if known.
+    pub fn aided_choice(
+        &self,
+        aids: Aids,
+        stop: &AtomicBool,
+    ) -> Option<((usize, usize), Option<Evaluation>)> {
+        if aids.solver {
+            if let Some(Solution {
+                outcome,
+                best_move: Some(best_move),
+                distance,
+            }) = endgame_solution(self, stop)
+            {
+                if outcome != PlayingState::Win(self.player.other()) {
+                    return Some((best_move, Some(Evaluation::Proven(outcome, distance))));
+                }
+            }
+        }
+        if aids.book {
+            if let Some(book_move) = book_move(self) {
+                return Some((book_move, None));
+            }
+        }
+        None
+    }
+
+    /// Returns the move `(ult_index, index)` chosen by a search of the given depth and heuristic,
+    /// after trying the given aids (see [`Morpion::aided_choice`]), with the evaluation of the position if known.
+    /// Returns `None` if the search was aborted.
+    pub fn choice_with(
+        &self,
+        depth: isize,
+        heuristic: &HeuristicFn,
+        aids: Aids,
+        table: &mut ScoreTable,
+        stop: &AtomicBool,
+    ) -> Option<((usize, usize), Option<Evaluation>)> {
+        if let Some(choice) = self.aided_choice(aids, stop) {
+            return Some(choice);
+        }
+        let mut best_move = (0, 0);
+        let mut best_score = 0;
         let mut max_score = isize::MIN;
-        for (index, child) in children.iter().enumerate() {
-            let mut score = match ai_level {
-                AILevel::Easy => alpha_beta(
-                    child,
-                    5,
-                    isize::MIN,
-                    isize::MAX,
-                    self.player,
-                    corner_heuristic,
-                ),
-                AILevel::Medium => alpha_beta(
-                    child,
-                    6,
-                    isize::MIN,
-                    isize::MAX,
-                    self.player,
-                    center_heuristic,
-                ),
-                AILevel::Hard => alpha_beta(
-                    child,
-                    6,
-                    isize::MIN,
-                    isize::MAX,
-                    self.player,
-                    everywhere_heuristic,
-                ),
-            };
-            score += score * 10 + noise(2);
-            if score > max_score {
-                max_score = score;
-                best_move_index = index;
+        for (ai_move, score) in self.score_moves_with(depth, heuristic, table, stop) {
+            let noisy_score = score + score * 10 + noise(2);
+            if noisy_score > max_score {
+                max_score = noisy_score;
+                best_move = ai_move;
+                best_score = score;
             }
         }
-        children[best_move_index].clone()
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        let x_score = match self.player {
+            Player::X => best_score,
+            Player::O => best_score.saturating_neg(),
+        };
+        Some((best_move, Some(Evaluation::Score(x_score))))
+    }
+
+    /// Evaluates every valid move with the search of the given AI level, keeping the scores found in `table`.
+    /// Returns the moves `(ult_index, index)` with their _Alpha-Beta_ score for the player to move.
+    pub fn score_moves(
+        &self,
+        ai_level: AILevel,
+        table: &mut ScoreTable,
+        stop: &AtomicBool,
+    ) -> Vec<((usize, usize), isize)> {
+        let (depth, heuristic) = search_params(ai_level);
+        self.score_moves_with(depth, heuristic, table, stop)
+    }
+
+    /// Same as [`Morpion::score_moves`], with a search of the given depth and heuristic.
+    pub fn score_moves_with(
+        &self,
+        depth: isize,
+        heuristic: &HeuristicFn,
+        table: &mut ScoreTable,
+        stop: &AtomicBool,
+    ) -> Vec<((usize, usize), isize)> {
+        let mut scores = Vec::new();
+        for (ult_index, index) in generate_moves(self) {
+            let mut child = self.clone();
+            child.play_at(ult_index, index);
+            let score = alpha_beta(
+                &child,
+                depth,
+                isize::MIN,
+                isize::MAX,
+                self.player,
+                heuristic,
+                table,
+                stop,
+            );
+            scores.push(((ult_index, index), score));
+        }
+        scores
+    }
+
+    /// Evaluates the position: proven result if the solver can find it,
+    /// otherwise score of the best move found by the search of the given AI level.
+    /// Returns `None` if the search was aborted.
+    pub fn evaluate(&self, ai_level: AILevel, stop: &AtomicBool) -> Option<Evaluation> {
+        if self.is_over() {
+            return Some(Evaluation::Proven(self.state.clone(), 0));
+        }
+        if let Some(solution) = endgame_solution(self, stop) {
+            return Some(Evaluation::Proven(solution.outcome, solution.distance));
+        }
+        let best_score = self
+            .score_moves(ai_level, &mut ScoreTable::new(), stop)
+            .into_iter()
+            .map(|(_, score)| score)
+            .max()?;
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(Evaluation::Score(match self.player {
+            Player::X => best_score,
+            Player::O => best_score.saturating_neg(),
+        }))
+    }
+
+    /// Returns the number of moves played since the beginning of the game.
+    pub fn ply(&self) -> usize {
+        self.board
+            .cells
+            .iter()
+            .flatten()
+            .filter(|cell| matches!(cell, CellState::Occupied(_)))
+            .count()
     }
 
     /// Evaluates the current game state.
@@ -348,6 +527,109 @@ impl Morpion {
         self.state = PlayingState::Continue;
         self.focused_big_cell = None;
     }
+
+    /// Returns a hash of the position (cells, player to move and focused big cell).
+    /// Unlike [`std::hash::Hash`], the value is stable and can be stored in files.
+    pub fn hash(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |byte: u8| {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        };
+        for cell in self.board.cells.iter().flatten() {
+            feed(match cell {
+                CellState::Free => 0,
+                CellState::Occupied(Player::X) => 1,
+                CellState::Occupied(Player::O) => 2,
+                CellState::Tie => 3,
+            });
+        }
+        feed(self.player as u8);
+        feed(self.focused_big_cell.map_or(9, |index| index as u8));
+        hash
+    }
+
+    /// Writes the position in the text notation read by [`Morpion::from_notation`].
+    pub fn to_notation(&self) -> String {
+        let cells: Vec<String> = self
+            .board
+            .cells
+            .iter()
+            .map(|big_cell| {
+                big_cell
+                    .iter()
+                    .map(|cell| match cell {
+                        CellState::Occupied(Player::X) => 'x',
+                        CellState::Occupied(Player::O) => 'o',
+                        _ => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+        format!(
+            "{} {} {}",
+            cells.join("/"),
+            self.player.to_string().to_lowercase(),
+            self.focused_big_cell
+                .map_or(String::from("-"), |index| index.to_string())
+        )
+    }
+
+    /// Reads a position from its text notation.
+    /// The notation is made of the nine big cells separated by `/` (nine `x`, `o` or `.` each,
+    /// in the same order as the indices), the player to move and the focused big cell (or `-`),
+    /// e.g. `........./....x..../........./........./........./........./........./........./......... o 4`.
+    pub fn from_notation(notation: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = notation.split_whitespace().collect();
+        let [cells, player, focused] = parts[..] else {
+            return Err(format!("expected 3 fields, got {}", parts.len()));
+        };
+        let mut morpion = Morpion::new();
+        let big_cells: Vec<&str> = cells.split('/').collect();
+        if big_cells.len() != 9 {
+            return Err(format!("expected 9 big cells, got {}", big_cells.len()));
+        }
+        for (ult_index, big_cell) in big_cells.iter().enumerate() {
+            if big_cell.chars().count() != 9 {
+                return Err(format!("big cell {} must have 9 cells", ult_index));
+            }
+            for (index, c) in big_cell.chars().enumerate() {
+                morpion.board.cells[ult_index][index] = match c {
+                    'x' | 'X' => CellState::Occupied(Player::X),
+                    'o' | 'O' => CellState::Occupied(Player::O),
+                    '.' => CellState::Free,
+                    _ => return Err(format!("invalid cell: {}", c)),
+                };
+            }
+            let cells = &morpion.board.cells[ult_index];
+            morpion.board.states[ult_index] = if is_won_by(cells, Player::X) {
+                CellState::Occupied(Player::X)
+            } else if is_won_by(cells, Player::O) {
+                CellState::Occupied(Player::O)
+            } else if all_occupied(cells) {
+                CellState::Tie
+            } else {
+                CellState::Free
+            };
+        }
+        morpion.player = match player {
+            "x" | "X" => Player::X,
+            "o" | "O" => Player::O,
+            _ => return Err(format!("invalid player: {}", player)),
+        };
+        morpion.focused_big_cell = match focused {
+            "-" => None,
+            _ => match focused.parse::<usize>() {
+                Ok(index) if index < 9 && morpion.board.states[index] == CellState::Free => {
+                    Some(index)
+                }
+                _ => return Err(format!("invalid focused big cell: {}", focused)),
+            },
+        };
+        morpion.state = morpion.check_playing_state();
+        Ok(morpion)
+    }
 }
 
 /// Represents the scene for rendering and managing the _Morpion_ game.
@@ -357,8 +639,29 @@ pub struct MorpionScene {
     text: Text,
     pub clicked: Option<(usize, usize)>,
     turn: usize,
-    ai_channel: Option<(Sender<Morpion>, Receiver<Morpion>)>,
-    ai_thread: Option<JoinHandle<()>>,
+    ai_worker: AIWorker,
+    /// Lets the AI think on the human's time in `PvAI` mode.
+    pub pondering: bool,
+    hint_worker: AIWorker,
+    /// Set when the player asks for a hint (with the key `H` or the hint button).
+    pub hint_requested: bool,
+    /// Hash of the position the hint is computed for.
+    hint_position: Option<u64>,
+    /// Suggested moves, best first.
+    hint: Option<Vec<(usize, usize)>>,
+    /// Tells if a human is to move in the game being played: only then can they ask for a hint.
+    human_turn: bool,
+    /// Shows the evaluation bar, and evaluates the positions for it.
+    pub show_evaluation: bool,
+    evaluation_worker: AIWorker,
+    /// Hash of the position the evaluation is computed for.
+    evaluation_position: Option<u64>,
+    /// Last evaluation of the position, shown in the evaluation bar.
+    evaluation: Option<Evaluation>,
+    /// Why the game was lost without being finished, e.g. an external engine crashed.
+    forfeit: Option<String>,
+    /// Recorded game shown in the `Replay` mode.
+    pub replay: Option<Replay>,
 }
 
 impl MorpionScene {
@@ -370,8 +673,19 @@ impl MorpionScene {
             text: Text::new("X begins !"),
             clicked: None,
             turn: 1,
-            ai_channel: None,
-            ai_thread: None,
+            ai_worker: AIWorker::new(),
+            pondering: true,
+            hint_worker: AIWorker::new(),
+            hint_requested: false,
+            hint_position: None,
+            hint: None,
+            human_turn: false,
+            show_evaluation: true,
+            evaluation_worker: AIWorker::new(),
+            evaluation_position: None,
+            evaluation: None,
+            forfeit: None,
+            replay: None,
         })
     }
 
@@ -380,8 +694,20 @@ impl MorpionScene {
         self.morpion.reset();
         self.turn = 1;
         self.text = Text::new("X begins !");
-        self.ai_channel = None;
-        self.ai_thread = None;
+        self.ai_worker.cancel();
+        self.clear_hint();
+        self.human_turn = false;
+        self.evaluation_worker.cancel();
+        self.evaluation_position = None;
+        self.evaluation = None;
+        self.forfeit = None;
+    }
+
+    /// Stops the AI workers, waiting for their threads to end.
+    pub fn shutdown(&mut self) {
+        self.ai_worker.shutdown();
+        self.hint_worker.shutdown();
+        self.evaluation_worker.shutdown();
     }
 
     /// Handles a player's move if they have clicked on a playable cell.
@@ -396,46 +722,178 @@ impl MorpionScene {
     }
 
     /// Handles the AI move logic using multithreading (because AI's computation can take time and freeze the UI).
-    /// Spawns a separate thread to compute the AI move asynchronously.
-    fn ai_plays(&mut self, ai_level: AILevel) {
-        //check if a thread is running
-        if let Some((_, rx)) = &self.ai_channel {
-            if let Ok(new_state) = rx.try_recv() {
-                self.morpion = new_state;
-                self.turn += 1;
-                //reset mpsc
-                self.ai_channel = None;
-                self.ai_thread = None;
+    /// The move is computed asynchronously by the AI worker, unless it was already found while pondering.
+    /// An external engine that fails to play loses the game.
+    fn ai_plays(&mut self, agent: AgentSpec) {
+        //check if a search is running
+        if self.ai_worker.is_busy() {
+            match self.ai_worker.try_result() {
+                Some(Ok(new_state)) => {
+                    if let Some(evaluation) = self.ai_worker.try_evaluation() {
+                        // The evaluation the AI found with its move spares a search
+                        self.evaluation_worker.cancel();
+                        self.evaluation_position = Some(new_state.hash());
+                        self.evaluation = Some(evaluation);
+                    }
+                    self.morpion = new_state;
+                    self.turn += 1;
+                }
+                Some(Err(e)) => {
+                    let player = self.morpion.player;
+                    self.forfeit = Some(format!("{} ({}) forfeits: {}", player, agent.name(), e));
+                    self.morpion.state = PlayingState::Win(player.other());
+                }
+                None => {}
             }
         }
-        //no thread is running
+        //the reply was computed during the human's turn
+        else if let Some(new_state) = self.ai_worker.take_pondered(&self.morpion) {
+            self.morpion = new_state;
+            self.turn += 1;
+        }
+        //no search is running
         else {
             //we can compute the next AI move with alpha-beta
-            let current_state = self.morpion.clone();
-            self.ai_channel = Some(channel());
-            let tx = self.ai_channel.as_ref().unwrap().0.clone();
+            self.ai_worker.start(self.morpion.clone(), agent);
+        }
+    }
 
-            //spawn the thread
-            self.ai_thread = Some(thread::spawn(move || {
-                //we can sleep if it's too fast, but it doesn't seem necessary:
-                //thread::sleep(Duration::from_secs(1));
-                let new_state = current_state.ai_move(ai_level);
-                //send AI move with the mpsc Sender
-                tx.send(new_state)
-                    .unwrap_or_else(|_| println!("channel killed"));
-            }));
+    /// Forgets the current hint and stops computing it.
+    fn clear_hint(&mut self) {
+        self.hint_worker.cancel();
+        self.hint_position = None;
+        self.hint = None;
+    }
+
+    /// Computes a hint in the background when the player asks for it, and collects it once found.
+    /// The hint is dropped as soon as the position changes.
+    fn update_hint(&mut self, human_turn: bool) {
+        let position = self.morpion.hash();
+        if self
+            .hint_position
+            .is_some_and(|hint_position| hint_position != position)
+        {
+            self.clear_hint();
+        }
+        if self.hint_requested && human_turn && self.hint_position.is_none() {
+            self.hint_worker
+                .analyse(self.morpion.clone(), AILevel::Hard);
+            self.hint_position = Some(position);
+        }
+        self.hint_requested = false;
+        if let Some(moves) = self.hint_worker.try_analysis() {
+            self.hint = Some(
+                moves
+                    .into_iter()
+                    .take(HINT_MOVES)
+                    .map(|(hint_move, _)| hint_move)
+                    .collect(),
+            );
+        }
+    }
+
+    /// Describes the suggested moves by rank, or the hint being computed.
+    /// The scores of the search are not shown, they only make sense to compare the moves.
+    fn hint_text(&self) -> String {
+        match (&self.hint, self.hint_position) {
+            (Some(moves), _) => {
+                let moves: Vec<String> = moves
+                    .iter()
+                    .enumerate()
+                    .map(|(rank, hint_move)| format!("{}. {}", rank + 1, format_move(*hint_move)))
+                    .collect();
+                format!("\nHint: {}", moves.join("  "))
+            }
+            (None, Some(_)) => String::from("\nLooking for a hint..."),
+            (None, None) => String::new(),
         }
     }
 
-    /// Updates the game state based on the current mode (`PvP`, `PvAI`, `AIvAI`).
+    /// Tells if the point is on the hint button, which is only shown when a human is to move.
+    pub fn hint_button_contains(&self, x: f32, y: f32) -> bool {
+        let (button_x, button_y, width, height) = HINT_BUTTON;
+        self.human_turn && Rect::new(button_x, button_y, width, height).contains([x, y])
+    }
+
+    /// Starts evaluating the position in the background after each move if the evaluation bar is shown,
+    /// and collects the evaluation. The previous evaluation stays shown until the new one is found.
+    /// Without `search`, e.g. between two AIs, only the evaluations the AIs give with their moves are shown.
+    fn update_evaluation(&mut self, search: bool) {
+        if !self.show_evaluation {
+            return;
+        }
+        let position = self.morpion.hash();
+        if search && self.evaluation_position != Some(position) {
+            self.evaluation_worker
+                .evaluate(self.morpion.clone(), AILevel::Hard);
+            self.evaluation_position = Some(position);
+        }
+        if let Some(evaluation) = self.evaluation_worker.try_evaluation() {
+            self.evaluation = Some(evaluation);
+        }
+    }
+
+    /// Lets the AI search its replies to the human's likely moves while the human is thinking.
+    /// External engines don't ponder.
+    fn ai_ponders(&mut self, agent: AgentSpec) {
+        if let (true, AgentSpec::Level(ai_level)) = (self.pondering, agent) {
+            self.ai_worker.ponder(self.morpion.clone(), ai_level);
+        }
+    }
+
+    /// Steps through the replayed game with the arrow keys, `Home` and `End`.
+    /// The evaluation bar follows the position shown.
+    fn update_replay(&mut self, ctx: &mut Context, state: &mut GameState) {
+        let Some(replay) = self.replay.as_mut() else {
+            *state = GameState::StartMenu;
+            return;
+        };
+        let keyboard = &ctx.keyboard;
+        if keyboard.is_key_just_pressed(KeyCode::Right) {
+            replay.next();
+        } else if keyboard.is_key_just_pressed(KeyCode::Left) {
+            replay.previous();
+        } else if keyboard.is_key_just_pressed(KeyCode::Home) {
+            replay.go_to(0);
+        } else if keyboard.is_key_just_pressed(KeyCode::End) {
+            replay.go_to(usize::MAX);
+        }
+        self.morpion = replay.position().clone();
+        self.text = Text::new(format!(
+            "{}\nLeft/Right to step, Q to go to the menu",
+            replay.text()
+        ));
+        while ctx.time.check_update_time(DESIRED_FPS) {
+            self.update_evaluation(true);
+        }
+        if ctx.keyboard.is_key_pressed(KeyCode::Q) {
+            self.replay = None;
+            self.reset();
+            *state = GameState::StartMenu;
+        }
+    }
+
+    /// Updates the game state based on the current mode (`PvP`, `PvAI`, `AIvAI`, `Replay`).
     /// Processes user inputs and updates the game logic accordingly.
     pub fn update(&mut self, ctx: &mut Context, state: &mut GameState, game_mode: GameMode) {
+        if game_mode == GameMode::Replay {
+            self.update_replay(ctx, state);
+            return;
+        }
+        // The AIs evaluate the positions themselves
+        let search = !matches!(game_mode, GameMode::AIvAI(_, _));
         while ctx.time.check_update_time(DESIRED_FPS) {
+            self.update_evaluation(search);
+            // Only a game being played waits for a human
+            self.human_turn = false;
             match self.morpion.state {
                 PlayingState::Continue => {
                     match game_mode {
                         GameMode::PvAI(o) => match self.morpion.player {
-                            Player::X => self.player_plays(),
+                            Player::X => {
+                                self.ai_ponders(o);
+                                self.player_plays();
+                            }
                             Player::O => self.ai_plays(o),
                         },
                         GameMode::PvP => match self.morpion.player {
@@ -446,9 +904,24 @@ impl MorpionScene {
                             Player::X => self.ai_plays(x),
                             Player::O => self.ai_plays(o),
                         },
+                        GameMode::Replay => unreachable!("replays are updated apart"),
+                    };
+
+                    self.human_turn = match game_mode {
+                        GameMode::PvP => true,
+                        GameMode::PvAI(_) => self.morpion.player == Player::X,
+                        GameMode::AIvAI(_, _) | GameMode::Replay => false,
                     };
+                    if self.human_turn && ctx.keyboard.is_key_pressed(KeyCode::H) {
+                        self.hint_requested = true;
+                    }
+                    self.update_hint(self.human_turn);
 
-                    self.text = Text::new(format!("{}'s turn !", self.morpion.player));
+                    self.text = Text::new(format!(
+                        "{}'s turn !{}",
+                        self.morpion.player,
+                        self.hint_text()
+                    ));
 
                     self.morpion.state = self.morpion.check_playing_state();
 
@@ -468,9 +941,13 @@ impl MorpionScene {
                     }
                 }
                 PlayingState::Win(player) => {
+                    let forfeit = match &self.forfeit {
+                        Some(forfeit) => format!("{}\n", forfeit),
+                        None => String::new(),
+                    };
                     self.text = Text::new(format!(
-                        "{} has won\nPress R to restart or Q to go to the menu",
-                        player
+                        "{}{} has won\nPress R to restart or Q to go to the menu",
+                        forfeit, player
                     ));
                     if ctx.keyboard.is_key_pressed(KeyCode::R) {
                         self.reset();
@@ -552,6 +1029,63 @@ impl Drawable for MorpionScene {
                 }
             }
         }
+        // Evaluation bar (X's share at the bottom)
+        if let (true, Some(evaluation)) = (self.show_evaluation, &self.evaluation) {
+            let height = 3.0 * BIG_CELL_SIZE;
+            let x_height = height * evaluation.x_share();
+            canvas.draw(
+                &Quad,
+                DrawParam::new()
+                    .dest_rect(Rect::new(
+                        EVAL_BAR_X,
+                        BORDER_PADDING,
+                        EVAL_BAR_WIDTH,
+                        height - x_height,
+                    ))
+                    .color(Color::from_rgb(55, 60, 75)),
+            );
+            canvas.draw(
+                &Quad,
+                DrawParam::new()
+                    .dest_rect(Rect::new(
+                        EVAL_BAR_X,
+                        BORDER_PADDING + height - x_height,
+                        EVAL_BAR_WIDTH,
+                        x_height,
+                    ))
+                    .color(Color::from_rgb(220, 220, 230)),
+            );
+            canvas.draw(
+                &Text::new(format!("Evaluation: {}", evaluation)),
+                DrawParam::from([BORDER_PADDING, BORDER_PADDING / 2.0 - 8.0]).color(Color::WHITE),
+            );
+        }
+        // Hint
+        if let Some(moves) = &self.hint {
+            for (rank, (ult_index, index)) in moves.iter().enumerate() {
+                let (x, y) = coord_from_ids(*ult_index, *index);
+                let alpha = if rank == 0 { 110 } else { 45 };
+                canvas.draw(
+                    &Quad,
+                    DrawParam::new()
+                        .dest_rect(Rect::new(x, y, CELL_SIZE, CELL_SIZE))
+                        .color(Color::from_rgba(90, 200, 120, alpha)),
+                );
+            }
+        }
+        if self.human_turn {
+            let (button_x, button_y, width, height) = HINT_BUTTON;
+            canvas.draw(
+                &Quad,
+                DrawParam::new()
+                    .dest_rect(Rect::new(button_x, button_y, width, height))
+                    .color(Color::from_rgb(55, 60, 75)),
+            );
+            canvas.draw(
+                &Text::new("Hint (H)"),
+                DrawParam::from([button_x + 25.0, button_y + 7.0]).color(Color::WHITE),
+            );
+        }
         // Text
         canvas.draw(
             &self.text,
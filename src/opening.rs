@@ -0,0 +1,66 @@
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use std::fs;
+use std::path::Path;
+
+use crate::ai::generate_moves;
+use crate::morpion::{parse_move, Morpion};
+
+/// Reads an opening suite: one opening per line, either a position written with [`Morpion::to_notation`]
+/// or moves from the start written with [`crate::morpion::format_move`] and separated by spaces.
+/// Lines starting with `#` are ignored.
+pub fn parse_openings(text: &str) -> Result<Vec<Morpion>, String> {
+    let mut openings = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let opening = if line.contains('/') {
+            Morpion::from_notation(line)?
+        } else {
+            let mut morpion = Morpion::new();
+            for m in line.split_whitespace() {
+                let (ult_index, index) = parse_move(m)?;
+                if morpion.is_over() || !morpion.index_is_playable(ult_index, index) {
+                    return Err(format!("illegal move {} in: {}", m, line));
+                }
+                morpion.play_at(ult_index, index);
+            }
+            morpion
+        };
+        if opening.is_over() {
+            return Err(format!("the game is over in: {}", line));
+        }
+        openings.push(opening);
+    }
+    if openings.is_empty() {
+        return Err("no opening".to_string());
+    }
+    Ok(openings)
+}
+
+/// Reads an opening suite from a file, see [`parse_openings`].
+pub fn load_openings(path: &Path) -> Result<Vec<Morpion>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_openings(&text)
+}
+
+/// Plays `plies` random moves from the start, never ending the game.
+pub fn random_opening(plies: usize, rng: &mut impl Rng) -> Morpion {
+    let mut morpion = Morpion::new();
+    for _ in 0..plies {
+        let moves: Vec<_> = generate_moves(&morpion)
+            .into_iter()
+            .filter(|&(ult_index, index)| {
+                let mut child = morpion.clone();
+                child.play_at(ult_index, index);
+                !child.is_over()
+            })
+            .collect();
+        let Some(&(ult_index, index)) = moves.choose(rng) else {
+            break;
+        };
+        morpion.play_at(ult_index, index);
+    }
+    morpion
+}
@@ -0,0 +1,66 @@
+use std::time::Instant;
+
+use crate::ai::generate_moves;
+use crate::morpion::Morpion;
+
+/// Counts the positions reached by playing every sequence of `depth` moves, the finished games stopping early.
+/// The counts from the start position are checked against known values to test the move generation.
+pub fn perft(morpion: &Morpion, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    if morpion.is_over() {
+        return 0;
+    }
+    generate_moves(morpion)
+        .into_iter()
+        .map(|(ult_index, index)| {
+            let mut child = morpion.clone();
+            child.play_at(ult_index, index);
+            perft(&child, depth - 1)
+        })
+        .sum()
+}
+
+/// Prints the number of positions of each depth up to `depth` from the position given in notation
+/// (see [`Morpion::from_notation`]), with the time taken.
+pub fn launch_perft(depth: usize, notation: &str) -> Result<(), String> {
+    let morpion =
+        Morpion::from_notation(notation).map_err(|e| format!("invalid position: {}", e))?;
+    println!("{}\n{}", morpion, morpion.to_notation());
+    for depth in 1..=depth {
+        let start = Instant::now();
+        let nodes = perft(&morpion, depth);
+        let elapsed = start.elapsed().as_secs_f64();
+        println!(
+            "=> perft {}: {} ({:.2} s, {:.0} nodes/s)",
+            depth,
+            nodes,
+            elapsed,
+            nodes as f64 / elapsed.max(1e-9)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_position() {
+        let morpion = Morpion::new();
+        let counts: Vec<u64> = (0..=4).map(|depth| perft(&morpion, depth)).collect();
+        assert_eq!(counts, [1, 81, 720, 6336, 55080]);
+    }
+
+    #[test]
+    fn finished_game() {
+        let morpion = Morpion::from_notation(
+            "xxx....../xxx....../xxx....../........./........./........./........./........./......... o -",
+        )
+        .unwrap();
+        assert_eq!(perft(&morpion, 0), 1);
+        assert_eq!(perft(&morpion, 2), 0);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::f64::consts::LN_10;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::agent::AgentSpec;
+use crate::fight::{fight_all, Pairing};
+use crate::morpion::{Player, PlayingState};
+use crate::tournament::Schedule;
+
+/// File where the ratings are written, and from which the menu reads them.
+pub const RATINGS_FILE: &str = "ratings.txt";
+/// Average rating of the agents, the Bradley–Terry model only gives relative strengths.
+const AVERAGE_RATING: f64 = 1500.0;
+/// Elo points per natural logarithm unit of strength.
+pub const ELO_SCALE: f64 = 400.0 / LN_10;
+/// Quantile of the normal distribution for 95% confidence intervals.
+pub const Z_95: f64 = 1.96;
+
+/// Rating of an agent in Elo, with the half-width of its 95% confidence interval.
+#[derive(Clone, Copy, Debug)]
+pub struct Rating {
+    pub elo: f64,
+    pub error: f64,
+}
+
+impl std::fmt::Display for Rating {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0} ± {:.0}", self.elo, self.error)
+    }
+}
+
+/// Results of a tournament between `n` agents.
+pub struct Results {
+    /// `points[i][j]` is the number of points scored by `i` against `j` (1 per win, 0.5 per tie).
+    points: Vec<Vec<f64>>,
+    /// `games[i][j]` is the number of games played between `i` and `j`.
+    games: Vec<Vec<f64>>,
+}
+
+impl Results {
+    fn new(n: usize) -> Self {
+        Self {
+            points: vec![vec![0.0; n]; n],
+            games: vec![vec![0.0; n]; n],
+        }
+    }
+
+    /// Records a game between `x` and `o`.
+    fn add(&mut self, x: usize, o: usize, result: &PlayingState) {
+        let x_points = match result {
+            PlayingState::Win(Player::X) => 1.0,
+            PlayingState::Win(Player::O) => 0.0,
+            _ => 0.5,
+        };
+        self.points[x][o] += x_points;
+        self.points[o][x] += 1.0 - x_points;
+        self.games[x][o] += 1.0;
+        self.games[o][x] += 1.0;
+    }
+}
+
+/// Plays `games` games between every pair of agents, each agent playing `X` in half of them.
+/// The games are spread across `threads` threads.
+pub fn round_robin(agents: &[AgentSpec], games: usize, threads: usize) -> Results {
+    let mut results = Results::new(agents.len());
+    let indices = Schedule::RoundRobin.games(agents.len(), games);
+    let pairings: Vec<Pairing> = indices
+        .iter()
+        .map(|&(x, o)| Pairing::new(agents[x], agents[o]))
+        .collect();
+    fight_all(&pairings, rand::random(), threads, |index, game| {
+        let (x, o) = indices[index];
+        println!("game {}/{}: {}", index % games + 1, games, game);
+        results.add(x, o, &game.result);
+    });
+    results
+}
+
+/// Inverts a square matrix with the _Gauss-Jordan elimination_.
+/// Returns `None` if the matrix is singular.
+fn invert(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut a: Vec<Vec<f64>> = matrix.to_vec();
+    let mut inverse: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&r1, &r2| a[r1][col].abs().total_cmp(&a[r2][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inverse.swap(col, pivot);
+        let factor = a[col][col];
+        for k in 0..n {
+            a[col][k] /= factor;
+            inverse[col][k] /= factor;
+        }
+        for row in 0..n {
+            if row != col {
+                let factor = a[row][col];
+                for k in 0..n {
+                    a[row][k] -= factor * a[col][k];
+                    inverse[row][k] -= factor * inverse[col][k];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
+
+/// Estimates the ratings with a _Bradley–Terry_ model, fitted with the MM algorithm.
+/// One virtual tie is added between every pair of agents, so an agent that never scored keeps a finite rating.
+/// The error bars come from the curvature of the likelihood at its maximum.
+pub fn bradley_terry(results: &Results) -> Vec<Rating> {
+    let n = results.points.len();
+    let mut points = results.points.clone();
+    let mut games = results.games.clone();
+    for i in 0..n {
+        for j in 0..n {
+            if i != j {
+                points[i][j] += 0.5;
+                games[i][j] += 1.0;
+            }
+        }
+    }
+    let wins: Vec<f64> = points.iter().map(|row| row.iter().sum()).collect();
+
+    let mut strengths = vec![1.0; n];
+    for _ in 0..10000 {
+        let mut next: Vec<f64> = (0..n)
+            .map(|i| {
+                let denominator: f64 = (0..n)
+                    .filter(|&j| j != i)
+                    .map(|j| games[i][j] / (strengths[i] + strengths[j]))
+                    .sum();
+                wins[i] / denominator
+            })
+            .collect();
+        // Normalizes the geometric mean to 1, so the average rating is fixed
+        let log_mean = next.iter().map(|s| s.ln()).sum::<f64>() / n as f64;
+        next.iter_mut().for_each(|s| *s /= log_mean.exp());
+        let change = next
+            .iter()
+            .zip(&strengths)
+            .map(|(a, b)| (a.ln() - b.ln()).abs())
+            .fold(0.0, f64::max);
+        strengths = next;
+        if change < 1e-10 {
+            break;
+        }
+    }
+
+    // The information matrix is only defined up to a common shift of the strengths,
+    // its pseudo-inverse is (H + J/n)^-1 - J/n where J is filled with ones
+    let mut information = vec![vec![1.0 / n as f64; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            if i != j {
+                let p = strengths[i] / (strengths[i] + strengths[j]);
+                let w = games[i][j] * p * (1.0 - p);
+                information[i][i] += w;
+                information[i][j] -= w;
+            }
+        }
+    }
+    let covariance = invert(&information);
+    (0..n)
+        .map(|i| Rating {
+            elo: AVERAGE_RATING + strengths[i].ln() * ELO_SCALE,
+            error: covariance.as_ref().map_or(f64::INFINITY, |covariance| {
+                Z_95 * (covariance[i][i] - 1.0 / n as f64).max(0.0).sqrt() * ELO_SCALE
+            }),
+        })
+        .collect()
+}
+
+/// Writes the ratings to a file: one `agent elo error` line per agent.
+pub fn save_ratings(agents: &[AgentSpec], ratings: &[Rating], path: &Path) -> io::Result<()> {
+    let text: String = agents
+        .iter()
+        .zip(ratings)
+        .map(|(agent, rating)| format!("{} {:.1} {:.1}\n", agent.name(), rating.elo, rating.error))
+        .collect();
+    fs::write(path, text)
+}
+
+/// Reads the ratings written by [`save_ratings`].
+/// Agents that are no longer configured are skipped.
+pub fn load_ratings(path: &Path) -> io::Result<HashMap<AgentSpec, Rating>> {
+    let text = fs::read_to_string(path)?;
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, line.to_string());
+    let mut ratings = HashMap::new();
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [name, elo, error] = fields[..] else {
+            return Err(invalid(line));
+        };
+        let elo = elo.parse::<f64>().map_err(|_| invalid(line))?;
+        let error = error.parse::<f64>().map_err(|_| invalid(line))?;
+        if let Some(agent) = AgentSpec::from_str(name) {
+            ratings.insert(agent, Rating { elo, error });
+        }
+    }
+    Ok(ratings)
+}
+
+/// Plays a round-robin between all the agents (AI levels and external engines) on `threads` threads,
+/// estimates their ratings and writes them to `output`.
+pub fn launch_rating(games: usize, threads: usize, output: &Path) -> Result<(), String> {
+    let agents = AgentSpec::all();
+    let results = round_robin(&agents, games, threads);
+    let ratings = bradley_terry(&results);
+    println!("-- ratings ({} games per pairing) --", games);
+    for (agent, rating) in agents.iter().zip(&ratings) {
+        println!("=> {}: {}", agent.name(), rating);
+    }
+    save_ratings(&agents, &ratings, output)
+        .map_err(|e| format!("can't write {}: {}", output.display(), e))
+}
@@ -0,0 +1,238 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::agent::AgentError;
+use crate::ai::Evaluation;
+use crate::fight::{Game, TimeControl};
+use crate::morpion::{format_move, parse_move, Morpion, Player, PlayingState};
+
+/// Returns the file name of the record of the game `index` out of `n`, numbered from 1 and
+/// padded so the files sort in the order of the games.
+pub fn record_name(index: usize, n: usize) -> String {
+    format!(
+        "game-{:0width$}.txt",
+        index + 1,
+        width = n.to_string().len()
+    )
+}
+
+/// Writes an evaluation from `X`'s point of view: `cp <score>`, `win <player> <plies>`, `tie <plies>`,
+/// or `-` if unknown.
+pub fn format_evaluation(evaluation: Option<&Evaluation>) -> String {
+    match evaluation {
+        None => "-".to_string(),
+        Some(Evaluation::Score(score)) => format!("cp {}", score),
+        Some(Evaluation::Proven(PlayingState::Win(player), distance)) => {
+            format!("win {} {}", player, distance)
+        }
+        Some(Evaluation::Proven(_, distance)) => format!("tie {}", distance),
+    }
+}
+
+/// Reads an evaluation written by [`format_evaluation`].
+pub fn parse_evaluation(s: &str) -> Result<Option<Evaluation>, String> {
+    let invalid = || format!("invalid evaluation: {}", s);
+    let tokens: Vec<&str> = s.split_whitespace().collect();
+    let number = |token: &str| token.parse::<usize>().map_err(|_| invalid());
+    Ok(match tokens[..] {
+        ["-"] => None,
+        ["cp", score] => Some(Evaluation::Score(score.parse().map_err(|_| invalid())?)),
+        ["win", player, distance] => Some(Evaluation::Proven(
+            PlayingState::Win(Player::from_str(player).ok_or_else(invalid)?),
+            number(distance)?,
+        )),
+        ["tie", distance] => Some(Evaluation::Proven(PlayingState::Tie, number(distance)?)),
+        _ => return Err(invalid()),
+    })
+}
+
+/// Writes a game record: `key value` lines for the agents (`x`, `o`), the `start` position if the game
+/// did not start from the beginning, the `seed`, the `time` control if any, the `result` (`X`, `O` or `tie`) and the `forfeit` if any,
+/// then one `<move> <time_ms> <evaluation>` line per move.
+/// Moves are written with [`format_move`] and evaluations with [`format_evaluation`].
+pub fn format_record(game: &Game) -> String {
+    let mut record = format!("x {}\no {}\n", game.x_agent, game.o_agent);
+    if let Some(start) = &game.start {
+        record += &format!("start {}\n", start);
+    }
+    record += &format!("seed {}\n", game.seed);
+    if let Some(time_control) = &game.time_control {
+        record += &format!("time {}\n", time_control);
+    }
+    record += &format!(
+        "result {}\n",
+        match &game.result {
+            PlayingState::Win(player) => player.to_string(),
+            _ => "tie".to_string(),
+        }
+    );
+    if let Some((player, reason)) = &game.forfeit {
+        record += &format!("forfeit {} {}\n", player, reason);
+    }
+    for ((m, time), evaluation) in game.moves.iter().zip(&game.times).zip(&game.evaluations) {
+        record += &format!(
+            "{} {:.1} {}\n",
+            format_move(*m),
+            time.as_secs_f64() * 1000.0,
+            format_evaluation(evaluation.as_ref())
+        );
+    }
+    record
+}
+
+/// Reads a game record written by [`format_record`], checking that its moves are legal.
+pub fn parse_record(text: &str) -> Result<Game, String> {
+    let (mut x_agent, mut o_agent, mut seed, mut result) = (None, None, None, None);
+    let (mut start, mut time_control, mut forfeit) = (None, None, None);
+    let (mut moves, mut times, mut evaluations) = (Vec::new(), Vec::new(), Vec::new());
+    let mut morpion = Morpion::new();
+    // Lines are not trimmed: the end of a forfeit reason may be spaces
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "x" => x_agent = Some(value.to_string()),
+            "o" => o_agent = Some(value.to_string()),
+            "start" if moves.is_empty() => {
+                morpion = Morpion::from_notation(value)?;
+                start = Some(value.to_string());
+            }
+            "seed" => {
+                seed = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid seed: {}", value))?,
+                )
+            }
+            "time" => {
+                time_control = Some(
+                    TimeControl::from_str(value)
+                        .ok_or(format!("invalid time control: {}", value))?,
+                )
+            }
+            "result" => {
+                result = Some(match value {
+                    "tie" => PlayingState::Tie,
+                    player => PlayingState::Win(
+                        Player::from_str(player).ok_or(format!("invalid result: {}", player))?,
+                    ),
+                })
+            }
+            "forfeit" => {
+                let invalid = || format!("invalid forfeit: {}", value);
+                let (player, reason) = value.split_once(' ').ok_or_else(invalid)?;
+                forfeit = Some((
+                    Player::from_str(player).ok_or_else(invalid)?,
+                    AgentError::from_str(reason).ok_or_else(invalid)?,
+                ));
+            }
+            _ => {
+                let (ult_index, index) = parse_move(key)?;
+                if morpion.is_over() || !morpion.index_is_playable(ult_index, index) {
+                    return Err(format!("illegal move {}", key));
+                }
+                morpion.play_at(ult_index, index);
+                let (time, evaluation) = value.split_once(' ').unwrap_or((value, "-"));
+                let time = time
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|ms| Duration::try_from_secs_f64(ms / 1000.0).ok())
+                    .ok_or(format!("invalid time: {}", time))?;
+                moves.push((ult_index, index));
+                times.push(time);
+                evaluations.push(parse_evaluation(evaluation)?);
+            }
+        }
+    }
+    Ok(Game {
+        x_agent: x_agent.ok_or("missing x")?,
+        o_agent: o_agent.ok_or("missing o")?,
+        start,
+        seed: seed.ok_or("missing seed")?,
+        time_control,
+        moves,
+        times,
+        evaluations,
+        result: result.ok_or("missing result")?,
+        forfeit,
+    })
+}
+
+/// Writes the record of a game to a file.
+pub fn save_record(game: &Game, path: &Path) -> io::Result<()> {
+    fs::write(path, format_record(game))
+}
+
+/// Reads the record of a game from a file, see [`parse_record`].
+pub fn load_record(path: &Path) -> Result<Game, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_record(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game started after the move `40`, lost by `O` sending an empty line after two moves.
+    fn game() -> Game {
+        let mut start = Morpion::new();
+        start.play_at(4, 0);
+        Game {
+            x_agent: "hard".to_string(),
+            o_agent: "gone-engine".to_string(),
+            start: Some(start.to_notation()),
+            seed: 42,
+            time_control: Some(TimeControl::Clock {
+                base: Duration::from_millis(2000),
+                increment: Duration::from_millis(50),
+            }),
+            moves: vec![(0, 4), (4, 4)],
+            times: vec![Duration::from_micros(12_500), Duration::from_micros(300)],
+            evaluations: vec![
+                Some(Evaluation::Score(-12)),
+                Some(Evaluation::Proven(PlayingState::Win(Player::X), 5)),
+            ],
+            result: PlayingState::Win(Player::X),
+            forfeit: Some((Player::O, AgentError::Protocol(String::new()))),
+        }
+    }
+
+    #[test]
+    fn evaluation_round_trip() {
+        for evaluation in [
+            None,
+            Some(Evaluation::Score(-37)),
+            Some(Evaluation::Proven(PlayingState::Win(Player::O), 3)),
+            Some(Evaluation::Proven(PlayingState::Tie, 8)),
+        ] {
+            let text = format_evaluation(evaluation.as_ref());
+            assert_eq!(parse_evaluation(&text), Ok(evaluation));
+        }
+        assert!(parse_evaluation("cp").is_err());
+        assert!(parse_evaluation("win Z 3").is_err());
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let game = game();
+        let text = format_record(&game);
+        let read = parse_record(&text).unwrap();
+        assert_eq!(format_record(&read), text);
+        assert_eq!((read.x_agent, read.o_agent), (game.x_agent, game.o_agent));
+        assert_eq!(read.start, game.start);
+        assert_eq!(read.time_control, game.time_control);
+        assert_eq!(read.moves, game.moves);
+        assert_eq!(read.evaluations, game.evaluations);
+        assert_eq!(read.result, game.result);
+        assert_eq!(read.forfeit, game.forfeit);
+    }
+
+    #[test]
+    fn illegal_record() {
+        let text = format_record(&game());
+        // The move 44 is played twice
+        assert!(parse_record(&format!("{}44 1.0 -\n", text)).is_err());
+        assert!(parse_record(&text.replace("seed 42\n", "")).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use crate::fight::Game;
+use crate::morpion::{format_move, Morpion, PlayingState};
+use crate::record::{format_evaluation, load_record};
+use crate::session::load_session;
+
+/// A recorded game, shown move by move.
+pub struct Replay {
+    game: Game,
+    /// Position before each move, then the final position.
+    positions: Vec<Morpion>,
+    /// Number of moves played in the position shown.
+    ply: usize,
+}
+
+impl Replay {
+    /// Replays the moves of a game, which were checked when its record was read.
+    pub fn new(game: Game) -> Self {
+        let mut morpion = game.start_position();
+        let mut positions = vec![morpion.clone()];
+        for &(ult_index, index) in &game.moves {
+            morpion.play_at(ult_index, index);
+            positions.push(morpion.clone());
+        }
+        Self {
+            game,
+            positions,
+            ply: 0,
+        }
+    }
+
+    /// Reads the game record at `path`, or the game `number` (from 1) of the fight session file at `path`.
+    pub fn load(path: &Path, number: Option<usize>) -> Result<Self, String> {
+        let game = match number {
+            None => load_record(path),
+            Some(number) => load_session(path).and_then(|(_, games)| {
+                games
+                    .into_iter()
+                    .nth(number.wrapping_sub(1))
+                    .ok_or(format!("no game {}", number))
+            }),
+        };
+        game.map(Replay::new)
+            .map_err(|e| format!("can't read {}: {}", path.display(), e))
+    }
+
+    /// Position after the moves played so far.
+    pub fn position(&self) -> &Morpion {
+        &self.positions[self.ply]
+    }
+
+    /// Goes to the position after `ply` moves, or to the end of the game if it has fewer moves.
+    pub fn go_to(&mut self, ply: usize) {
+        self.ply = ply.min(self.game.moves.len());
+    }
+
+    /// Goes one move forward.
+    pub fn next(&mut self) {
+        self.go_to(self.ply + 1);
+    }
+
+    /// Goes one move back.
+    pub fn previous(&mut self) {
+        self.go_to(self.ply.saturating_sub(1));
+    }
+
+    /// Describes the game and the last move played, with its thinking time and the evaluation of its agent.
+    pub fn text(&self) -> String {
+        let game = &self.game;
+        let mut text = format!("{} (X) vs {} (O)\n", game.x_agent, game.o_agent);
+        text += &match self.ply.checked_sub(1) {
+            None => format!("start, {} moves", game.moves.len()),
+            Some(last) => format!(
+                "move {}/{}: {} ({:.1} ms, eval {})",
+                self.ply,
+                game.moves.len(),
+                format_move(game.moves[last]),
+                game.times[last].as_secs_f64() * 1000.0,
+                format_evaluation(game.evaluations[last].as_ref())
+            ),
+        };
+        if self.ply == game.moves.len() {
+            text += &match &game.result {
+                PlayingState::Win(player) => format!("\n{} has won", player),
+                _ => "\nTie".to_string(),
+            };
+            if let Some((player, reason)) = &game.forfeit {
+                text += &format!(", {} forfeits: {}", player, reason);
+            }
+        }
+        text
+    }
+
+    /// Prints every position of the game, with the move that led to it.
+    pub fn print(&mut self) {
+        for ply in 0..self.positions.len() {
+            self.go_to(ply);
+            println!("{}\n{}", self.text(), self.position());
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::time::Duration;
+
+use crate::fight::Game;
+use crate::morpion::{Player, PlayingState};
+use crate::stats::{Score, ThinkingTime};
+
+/// Columns of the rows of the games.
+const GAME_COLUMNS: [&str; 11] = [
+    "index",
+    "x",
+    "o",
+    "start",
+    "result",
+    "forfeit",
+    "moves",
+    "duration_ms",
+    "x_time_ms",
+    "o_time_ms",
+    "seed",
+];
+/// Columns of the summary, from the point of view of one agent.
+const SUMMARY_COLUMNS: [&str; 11] = [
+    "agent",
+    "games",
+    "wins",
+    "losses",
+    "ties",
+    "elo",
+    "elo_low",
+    "elo_high",
+    "los",
+    "ms_per_move",
+    "time_forfeits",
+];
+
+/// Format of a machine-readable report of fights.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// Converts a string into a [`Format`].
+    /// Returns `None` if the input string does not match any format.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the result of a game as written in the reports: `X`, `O` or `tie`.
+fn result_name(result: &PlayingState) -> String {
+    match result {
+        PlayingState::Win(player) => player.to_string(),
+        _ => "tie".to_string(),
+    }
+}
+
+/// A value of a report.
+enum Value {
+    Number(String),
+    Text(String),
+    Missing,
+}
+
+impl Value {
+    /// Returns the value if it is a finite number, [`Value::Missing`] otherwise.
+    fn float(x: f64) -> Self {
+        if x.is_finite() {
+            Value::Number(format!("{:.1}", x))
+        } else {
+            Value::Missing
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Value::Number(number) => number.clone(),
+            Value::Text(text) => {
+                let mut json = String::from("\"");
+                for c in text.chars() {
+                    match c {
+                        '"' => json.push_str("\\\""),
+                        '\\' => json.push_str("\\\\"),
+                        c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => json.push(c),
+                    }
+                }
+                json.push('"');
+                json
+            }
+            Value::Missing => "null".to_string(),
+        }
+    }
+
+    fn csv(&self) -> String {
+        match self {
+            Value::Number(number) => number.clone(),
+            Value::Text(text) if text.contains([',', '"', '\n']) => {
+                format!("\"{}\"", text.replace('"', "\"\""))
+            }
+            Value::Text(text) => text.clone(),
+            Value::Missing => String::new(),
+        }
+    }
+}
+
+/// Returns the values of the row of a game, see [`GAME_COLUMNS`].
+fn game_row(index: usize, game: &Game) -> Vec<Value> {
+    let millis = |time: Duration| Value::float(time.as_secs_f64() * 1000.0);
+    vec![
+        Value::Number((index + 1).to_string()),
+        Value::Text(game.x_agent.clone()),
+        Value::Text(game.o_agent.clone()),
+        game.start.clone().map_or(Value::Missing, Value::Text),
+        Value::Text(result_name(&game.result)),
+        game.forfeit
+            .as_ref()
+            .map_or(Value::Missing, |(player, reason)| {
+                Value::Text(format!("{} {}", player, reason))
+            }),
+        Value::Number(game.moves.len().to_string()),
+        millis(game.times.iter().sum()),
+        millis(game.time(Player::X)),
+        millis(game.time(Player::O)),
+        Value::Number(game.seed.to_string()),
+    ]
+}
+
+/// Returns the values of the summary from the point of view of `agent`, see [`SUMMARY_COLUMNS`].
+/// Both sides of a game being played by `agent`, it is taken as `X`.
+fn summary_row(games: &[Game], agent: &str) -> Vec<Value> {
+    let mut score = Score::default();
+    let mut time = ThinkingTime::default();
+    for game in games {
+        let player = if game.x_agent == agent {
+            Player::X
+        } else {
+            Player::O
+        };
+        score.add(&game.result, player);
+        time.add(game, player);
+    }
+    let elo = score.elo();
+    vec![
+        Value::Text(agent.to_string()),
+        Value::Number(games.len().to_string()),
+        Value::Number(score.wins.to_string()),
+        Value::Number(score.losses.to_string()),
+        Value::Number(score.draws.to_string()),
+        Value::float(elo.map_or(f64::NAN, |elo| elo.elo)),
+        Value::float(elo.map_or(f64::NAN, |elo| elo.low)),
+        Value::float(elo.map_or(f64::NAN, |elo| elo.high)),
+        Value::Number(format!("{:.3}", score.los())),
+        Value::float(time.average().as_secs_f64() * 1000.0),
+        Value::Number(time.time_forfeits.to_string()),
+    ]
+}
+
+/// Writes a JSON object.
+fn json_object(columns: &[&str], values: &[Value]) -> String {
+    let fields: Vec<String> = columns
+        .iter()
+        .zip(values)
+        .map(|(column, value)| format!("\"{}\": {}", column, value.json()))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+/// Writes a CSV line.
+fn csv_line(values: impl Iterator<Item = String>) -> String {
+    values.collect::<Vec<String>>().join(",") + "\n"
+}
+
+/// Writes the report of a series of fights: one row per game, then a summary from the point of view of `agent`.
+/// In JSON, an object with a `games` array and a `summary` object. In CSV, the table of the games,
+/// an empty line, then the table of the summary. Missing values are `null` in JSON and empty in CSV.
+pub fn report(format: Format, games: &[Game], agent: &str) -> String {
+    let rows: Vec<Vec<Value>> = games
+        .iter()
+        .enumerate()
+        .map(|(index, game)| game_row(index, game))
+        .collect();
+    let summary = summary_row(games, agent);
+    match format {
+        Format::Json => {
+            let rows: Vec<String> = rows
+                .iter()
+                .map(|row| format!("    {}", json_object(&GAME_COLUMNS, row)))
+                .collect();
+            format!(
+                "{{\n  \"games\": [\n{}\n  ],\n  \"summary\": {}\n}}\n",
+                rows.join(",\n"),
+                json_object(&SUMMARY_COLUMNS, &summary)
+            )
+        }
+        Format::Csv => {
+            let mut csv = csv_line(GAME_COLUMNS.iter().map(|column| column.to_string()));
+            for row in rows {
+                csv += &csv_line(row.iter().map(Value::csv));
+            }
+            csv += "\n";
+            csv += &csv_line(SUMMARY_COLUMNS.iter().map(|column| column.to_string()));
+            csv += &csv_line(summary.iter().map(Value::csv));
+            csv
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::AgentError;
+
+    fn games() -> Vec<Game> {
+        let game = |x_agent: &str, o_agent: &str, result, forfeit| Game {
+            x_agent: x_agent.to_string(),
+            o_agent: o_agent.to_string(),
+            start: None,
+            seed: 7,
+            time_control: None,
+            moves: vec![(4, 4), (4, 0)],
+            times: vec![Duration::from_millis(10), Duration::from_millis(30)],
+            evaluations: vec![None, None],
+            result,
+            forfeit,
+        };
+        vec![
+            game("easy", "bot \"2\", v1", PlayingState::Win(Player::O), None),
+            game(
+                "bot \"2\", v1",
+                "easy",
+                PlayingState::Win(Player::X),
+                Some((Player::O, AgentError::Timeout(Duration::from_millis(100)))),
+            ),
+        ]
+    }
+
+    #[test]
+    fn csv_report() {
+        let csv = report(Format::Csv, &games(), "easy");
+        let expected = [
+            "index,x,o,start,result,forfeit,moves,duration_ms,x_time_ms,o_time_ms,seed",
+            r#"1,easy,"bot ""2"", v1",,O,,2,40.0,10.0,30.0,7"#,
+            r#"2,"bot ""2"", v1",easy,,X,O no move within 100 ms,2,40.0,10.0,30.0,7"#,
+            "",
+            "agent,games,wins,losses,ties,elo,elo_low,elo_high,los,ms_per_move,time_forfeits",
+            "easy,2,0,2,0,,,,0.079,20.0,1",
+        ];
+        assert_eq!(csv, expected.join("\n") + "\n");
+    }
+
+    #[test]
+    fn json_report() {
+        let json = report(Format::Json, &games(), "easy");
+        let expected = [
+            "{",
+            r#"  "games": ["#,
+            r#"    {"index": 1, "x": "easy", "o": "bot \"2\", v1", "start": null, "result": "O", "forfeit": null, "moves": 2, "duration_ms": 40.0, "x_time_ms": 10.0, "o_time_ms": 30.0, "seed": 7},"#,
+            r#"    {"index": 2, "x": "bot \"2\", v1", "o": "easy", "start": null, "result": "X", "forfeit": "O no move within 100 ms", "moves": 2, "duration_ms": 40.0, "x_time_ms": 10.0, "o_time_ms": 30.0, "seed": 7}"#,
+            "  ],",
+            r#"  "summary": {"agent": "easy", "games": 2, "wins": 0, "losses": 2, "ties": 0, "elo": null, "elo_low": null, "elo_high": null, "los": 0.079, "ms_per_move": 20.0, "time_forfeits": 1}"#,
+            "}",
+        ];
+        assert_eq!(json, expected.join("\n") + "\n");
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ai::{generate_moves, WINNING_WEIGHT};
+use crate::heuristic::Heuristic;
+use crate::morpion::{format_move, Morpion, PlayingState};
+
+/// A move `(ult_index, index)`.
+pub type Move = (usize, usize);
+
+/// Score of a position where the player to move has already lost.
+/// Won positions score `±(MATE - plies)`, far above any heuristic score.
+const MATE: isize = WINNING_WEIGHT * 100;
+/// Scores beyond this bound are proven wins or losses.
+const MATE_BOUND: isize = MATE - 100;
+/// A game never lasts more than 81 moves.
+const MAX_DEPTH: isize = 81;
+/// Approximate size of a transposition table entry, in bytes.
+const ENTRY_SIZE: usize = 48;
+/// The time limit is checked every this many nodes.
+const TIME_CHECK_NODES: usize = 1024;
+
+/// Limits of a search, it goes on until the first one is reached (or until it is stopped).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Limits {
+    /// Maximum depth of the iterative deepening, in plies.
+    pub depth: Option<isize>,
+    /// Maximum thinking time.
+    pub movetime: Option<Duration>,
+    /// Maximum number of visited nodes.
+    pub nodes: Option<usize>,
+}
+
+/// Progress of a search, reported after each completed depth.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: isize,
+    /// Score from the point of view of the player to move.
+    pub score: isize,
+    pub nodes: usize,
+    pub time: Duration,
+    /// Principal variation: the best line found, starting with the best move.
+    pub pv: Vec<Move>,
+}
+
+impl std::fmt::Display for SearchInfo {
+    /// Writes the info line of the engine protocol, e.g. `info depth 5 score cp 12 nodes 3456 time 20 pv 40 41`.
+    /// Proven results are written `score mate N`, `N` being the number of plies, negative if losing.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "info depth {} score ", self.depth)?;
+        if self.score.abs() > MATE_BOUND {
+            write!(
+                f,
+                "mate {}",
+                self.score.signum() * (MATE - self.score.abs())
+            )?;
+        } else {
+            write!(f, "cp {}", self.score)?;
+        }
+        write!(f, " nodes {} time {}", self.nodes, self.time.as_millis())?;
+        if !self.pv.is_empty() {
+            let pv: Vec<String> = self.pv.iter().map(|m| format_move(*m)).collect();
+            write!(f, " pv {}", pv.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Bound stored in the transposition table, since alpha-beta scores are not always exact.
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// Transposition table entry.
+#[derive(Clone, Copy)]
+struct Entry {
+    depth: isize,
+    score: isize,
+    bound: Bound,
+    best_move: Option<Move>,
+}
+
+/// Transposition table, cleared when full.
+struct Table {
+    entries: HashMap<u64, Entry>,
+    capacity: usize,
+}
+
+impl Table {
+    fn new(bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity: (bytes / ENTRY_SIZE).max(1),
+        }
+    }
+
+    fn insert(&mut self, key: u64, entry: Entry) {
+        if self.entries.len() >= self.capacity {
+            self.entries.clear();
+        }
+        self.entries.insert(key, entry);
+    }
+}
+
+/// Proven scores are stored relative to the node in the transposition table.
+fn to_table(score: isize, ply: isize) -> isize {
+    if score.abs() > MATE_BOUND {
+        score + score.signum() * ply
+    } else {
+        score
+    }
+}
+
+/// Inverse of [`to_table`].
+fn from_table(score: isize, ply: isize) -> isize {
+    if score.abs() > MATE_BOUND {
+        score - score.signum() * ply
+    } else {
+        score
+    }
+}
+
+/// State shared by the threads of a search.
+struct Shared<'a> {
+    heuristic: &'a Heuristic,
+    limits: Limits,
+    stop: &'a AtomicBool,
+    nodes: AtomicUsize,
+    start: Instant,
+}
+
+impl Shared<'_> {
+    /// Counts a node and tells if the search must stop, setting the stop flag when a limit is reached.
+    fn should_stop(&self) -> bool {
+        if self.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        let nodes = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+        let over = self.limits.nodes.is_some_and(|max| nodes > max)
+            || (nodes.is_multiple_of(TIME_CHECK_NODES)
+                && self
+                    .limits
+                    .movetime
+                    .is_some_and(|movetime| self.start.elapsed() >= movetime));
+        if over {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        over
+    }
+}
+
+/// Returns the moves of the node, with `first` in front if it is given.
+fn ordered_moves(node: &Morpion, first: Option<Move>) -> Vec<Move> {
+    let mut moves = generate_moves(node);
+    if let Some(position) = first.and_then(|first| moves.iter().position(|m| *m == first)) {
+        moves[..=position].rotate_right(1);
+    }
+    moves
+}
+
+/// Negamax with alpha-beta pruning and a transposition table.
+/// Returns the score from the point of view of the player to move and fills `pv` with the best line,
+/// or `None` if the search is stopped.
+#[allow(clippy::too_many_arguments)]
+fn negamax(
+    shared: &Shared,
+    table: &mut Table,
+    node: &Morpion,
+    depth: isize,
+    ply: isize,
+    mut alpha: isize,
+    mut beta: isize,
+    pv: &mut Vec<Move>,
+) -> Option<isize> {
+    pv.clear();
+    match node.state {
+        PlayingState::Win(_) => return Some(-(MATE - ply)),
+        PlayingState::Tie => return Some(0),
+        PlayingState::Continue => {}
+    }
+    if shared.should_stop() {
+        return None;
+    }
+    if depth == 0 {
+        return Some(shared.heuristic.evaluate(node, node.player));
+    }
+    let key = node.hash();
+    let mut tt_move = None;
+    if let Some(entry) = table.entries.get(&key) {
+        tt_move = entry.best_move;
+        if entry.depth >= depth {
+            let score = from_table(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return Some(score),
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Upper => beta = beta.min(score),
+            }
+            if alpha >= beta {
+                return Some(score);
+            }
+        }
+    }
+    let original_alpha = alpha;
+    let mut best = -MATE - 1;
+    let mut best_move = None;
+    let mut child_pv = Vec::new();
+    for (ult_index, index) in ordered_moves(node, tt_move) {
+        let mut child = node.clone();
+        child.play_at(ult_index, index);
+        let score = -negamax(
+            shared,
+            table,
+            &child,
+            depth - 1,
+            ply + 1,
+            -beta,
+            -alpha,
+            &mut child_pv,
+        )?;
+        if score > best {
+            best = score;
+            best_move = Some((ult_index, index));
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push((ult_index, index));
+                pv.extend_from_slice(&child_pv);
+            }
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    let bound = if best <= original_alpha {
+        Bound::Upper
+    } else if best >= beta {
+        Bound::Lower
+    } else {
+        Bound::Exact
+    };
+    table.insert(
+        key,
+        Entry {
+            depth,
+            score: to_table(best, ply),
+            bound,
+            best_move,
+        },
+    );
+    Some(best)
+}
+
+/// Iterative deepening search, configurable through the engine protocol (see [`crate::engine`]).
+/// With several threads, the moves of the root are shared between them, each one having its own
+/// transposition table.
+pub struct Searcher {
+    tables: Vec<Table>,
+    hash_size: usize,
+}
+
+impl Searcher {
+    /// Creates a searcher using `threads` threads and `hash_size` megabytes of transposition tables.
+    pub fn new(hash_size: usize, threads: usize) -> Self {
+        let threads = threads.max(1);
+        Self {
+            tables: (0..threads)
+                .map(|_| Table::new(hash_size * 1024 * 1024 / threads))
+                .collect(),
+            hash_size,
+        }
+    }
+
+    /// Changes the size of the transposition tables, which are cleared.
+    pub fn set_hash_size(&mut self, hash_size: usize) {
+        *self = Self::new(hash_size, self.tables.len());
+    }
+
+    /// Changes the number of threads, the transposition tables are cleared.
+    pub fn set_threads(&mut self, threads: usize) {
+        *self = Self::new(self.hash_size, threads);
+    }
+
+    /// Forgets everything learned from previous searches.
+    pub fn clear(&mut self) {
+        self.tables
+            .iter_mut()
+            .for_each(|table| table.entries.clear());
+    }
+
+    /// Searches the best move of `node` until a limit is reached or `stop` is set.
+    /// `on_info` is called after each completed depth.
+    /// Returns `None` if there is no move to play.
+    pub fn search(
+        &mut self,
+        node: &Morpion,
+        heuristic: &Heuristic,
+        limits: Limits,
+        stop: &AtomicBool,
+        mut on_info: impl FnMut(&SearchInfo),
+    ) -> Option<Move> {
+        if node.is_over() {
+            return None;
+        }
+        let mut order = generate_moves(node);
+        let mut best_move = *order.first()?;
+        let shared = Shared {
+            heuristic,
+            limits,
+            stop,
+            nodes: AtomicUsize::new(0),
+            start: Instant::now(),
+        };
+        for depth in 1..=limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH) {
+            let Some((score, pv)) = self.search_root(&shared, node, &order, depth) else {
+                break;
+            };
+            best_move = pv[0];
+            let position = order.iter().position(|m| *m == best_move).unwrap();
+            order[..=position].rotate_right(1);
+            on_info(&SearchInfo {
+                depth,
+                score,
+                nodes: shared.nodes.load(Ordering::Relaxed),
+                time: shared.start.elapsed(),
+                pv,
+            });
+            if score.abs() > MATE_BOUND && MATE - score.abs() <= depth {
+                // The result is proven, searching deeper won't change it
+                break;
+            }
+        }
+        Some(best_move)
+    }
+
+    /// Searches every move of the root at the given depth, sharing them between the threads.
+    /// Returns the best score with its principal variation, or `None` if the search was stopped.
+    fn search_root(
+        &mut self,
+        shared: &Shared,
+        node: &Morpion,
+        order: &[Move],
+        depth: isize,
+    ) -> Option<(isize, Vec<Move>)> {
+        let next = AtomicUsize::new(0);
+        let best: Mutex<Option<(isize, Vec<Move>)>> = Mutex::new(None);
+        thread::scope(|scope| {
+            for table in self.tables.iter_mut() {
+                let (next, best) = (&next, &best);
+                scope.spawn(move || {
+                    let mut child_pv = Vec::new();
+                    while let Some(&(ult_index, index)) =
+                        order.get(next.fetch_add(1, Ordering::Relaxed))
+                    {
+                        let alpha = best
+                            .lock()
+                            .unwrap()
+                            .as_ref()
+                            .map_or(-MATE - 1, |(score, _)| *score);
+                        let mut child = node.clone();
+                        child.play_at(ult_index, index);
+                        let Some(score) = negamax(
+                            shared,
+                            table,
+                            &child,
+                            depth - 1,
+                            1,
+                            -MATE - 1,
+                            -alpha,
+                            &mut child_pv,
+                        ) else {
+                            break;
+                        };
+                        let score = -score;
+                        let mut best = best.lock().unwrap();
+                        if best
+                            .as_ref()
+                            .is_none_or(|(best_score, _)| score > *best_score)
+                        {
+                            let mut pv = vec![(ult_index, index)];
+                            pv.extend_from_slice(&child_pv);
+                            *best = Some((score, pv));
+                        }
+                    }
+                });
+            }
+        });
+        if shared.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        best.into_inner().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heuristic::Profile;
+
+    #[test]
+    fn search_finds_a_mate_in_one() {
+        let node = Morpion::from_notation(
+            "xxx....../xxx....../xx......./........./........./........./........./........./......... x 2",
+        )
+        .unwrap();
+        let mut last_info = None;
+        let best_move = Searcher::new(1, 1).search(
+            &node,
+            &Profile::default().heuristic,
+            Limits {
+                depth: Some(3),
+                ..Default::default()
+            },
+            &AtomicBool::new(false),
+            |info| last_info = Some(info.clone()),
+        );
+        assert_eq!(best_move, Some((2, 2)));
+        assert_eq!(last_info.unwrap().score, MATE - 1);
+    }
+}
@@ -0,0 +1,196 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::fight::{Game, Openings, TimeControl};
+use crate::record::{format_record, parse_record};
+
+/// What decides the games of a series of fights, so that an interrupted series can be finished later.
+#[derive(Clone, Debug)]
+pub struct Session {
+    /// Names of the agents, see [`crate::agent::AgentSpec::name`], only resolved when the session is resumed.
+    pub x_agent: String,
+    pub o_agent: String,
+    /// Number of games of the series.
+    pub games: usize,
+    /// Seed of the first game, see [`crate::fight::FightOptions`].
+    pub seed: u64,
+    pub openings: Option<Openings>,
+    pub time_control: Option<TimeControl>,
+}
+
+/// Writes the header of a session file: `key value` lines for the agents (`x`, `o`), the number of `games`,
+/// the `seed`, the `openings` (`suite <path>` or `random <plies>`) and the `time` control if any.
+fn format_header(session: &Session) -> String {
+    let mut header = format!(
+        "x {}\no {}\ngames {}\nseed {}\n",
+        session.x_agent, session.o_agent, session.games, session.seed
+    );
+    match &session.openings {
+        Some(Openings::Suite(path)) => header += &format!("openings suite {}\n", path.display()),
+        Some(Openings::Random(plies)) => header += &format!("openings random {}\n", plies),
+        None => (),
+    }
+    if let Some(time_control) = &session.time_control {
+        header += &format!("time {}\n", time_control);
+    }
+    header
+}
+
+/// Writes a finished game of a session: a `game <number>` line, its game record, then an `end` line.
+fn format_game(index: usize, game: &Game) -> String {
+    format!("game {}\n{}end\n", index + 1, format_record(game))
+}
+
+/// Reads the header of a session file written by [`format_header`].
+fn parse_header(text: &str) -> Result<Session, String> {
+    let (mut x_agent, mut o_agent, mut games, mut seed) = (None, None, None, None);
+    let (mut openings, mut time_control) = (None, None);
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let invalid = || format!("invalid {}: {}", key, value);
+        match key {
+            "x" => x_agent = Some(value.to_string()),
+            "o" => o_agent = Some(value.to_string()),
+            "games" => games = Some(value.parse::<usize>().map_err(|_| invalid())?),
+            "seed" => seed = Some(value.parse::<u64>().map_err(|_| invalid())?),
+            "openings" => {
+                openings = Some(match value.split_once(' ') {
+                    Some(("suite", path)) => Openings::Suite(PathBuf::from(path)),
+                    Some(("random", plies)) => {
+                        Openings::Random(plies.parse::<usize>().map_err(|_| invalid())?)
+                    }
+                    _ => return Err(invalid()),
+                })
+            }
+            "time" => time_control = Some(TimeControl::from_str(value).ok_or_else(invalid)?),
+            _ => return Err(format!("unknown line: {}", line)),
+        }
+    }
+    Ok(Session {
+        x_agent: x_agent.ok_or("missing x")?,
+        o_agent: o_agent.ok_or("missing o")?,
+        games: games.ok_or("missing games")?,
+        seed: seed.ok_or("missing seed")?,
+        openings,
+        time_control,
+    })
+}
+
+/// Reads a session file: its header and the games finished, in order.
+/// A last game without its `end` line was being written when the session was interrupted, it is left out.
+pub fn parse_session(text: &str) -> Result<(Session, Vec<Game>), String> {
+    let mut blocks = text.split("\ngame ");
+    let session = parse_header(blocks.next().unwrap_or_default())?;
+    let mut games = Vec::new();
+    for block in blocks {
+        let Some((number, record)) = block.split_once('\n') else {
+            break;
+        };
+        let Some(record) = record.trim_end().strip_suffix("\nend") else {
+            break;
+        };
+        if number.trim() != (games.len() + 1).to_string() {
+            return Err(format!(
+                "game {} found instead of {}",
+                number,
+                games.len() + 1
+            ));
+        }
+        games.push(parse_record(record).map_err(|e| format!("game {}: {}", number, e))?);
+    }
+    if games.len() > session.games {
+        return Err(format!(
+            "{} games found out of {}",
+            games.len(),
+            session.games
+        ));
+    }
+    Ok((session, games))
+}
+
+/// Reads a session file, see [`parse_session`].
+pub fn load_session(path: &Path) -> Result<(Session, Vec<Game>), String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_session(&text)
+}
+
+/// Writes a session file with the games finished so far.
+pub fn save_session(path: &Path, session: &Session, games: &[Game]) -> io::Result<()> {
+    let mut text = format_header(session);
+    for (index, game) in games.iter().enumerate() {
+        text += &format_game(index, game);
+    }
+    fs::write(path, text)
+}
+
+/// Adds the game `index` at the end of a session file, as soon as it is finished.
+pub fn append_game(path: &Path, index: usize, game: &Game) -> io::Result<()> {
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    file.write_all(format_game(index, game).as_bytes())?;
+    file.sync_data()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        Session {
+            x_agent: "hard".to_string(),
+            o_agent: "profiles/aggressive.txt".to_string(),
+            games: 4,
+            seed: 7,
+            openings: Some(Openings::Random(4)),
+            time_control: Some(TimeControl::PerMove(std::time::Duration::from_millis(100))),
+        }
+    }
+
+    /// A finished game, then a game lost before its first move.
+    fn games() -> Vec<Game> {
+        let first = "x hard\no profiles/aggressive.txt\nseed 7\nresult O\n44 3.5 cp 10\n40 1.2 -\n";
+        let second =
+            "x profiles/aggressive.txt\no hard\nseed 8\nresult O\nforfeit X protocol error: \n";
+        vec![parse_record(first).unwrap(), parse_record(second).unwrap()]
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let text =
+            format_header(&session()) + &format_game(0, &games()[0]) + &format_game(1, &games()[1]);
+        let (session, games) = parse_session(&text).unwrap();
+        let mut written = format_header(&session);
+        for (index, game) in games.iter().enumerate() {
+            written += &format_game(index, game);
+        }
+        assert_eq!(written, text);
+        assert_eq!(games.len(), 2);
+        assert!(games[1].forfeit.is_some());
+    }
+
+    #[test]
+    fn truncated_session() {
+        let complete = format_header(&session()) + &format_game(0, &games()[0]);
+        let interrupted = complete.clone() + &format_game(1, &games()[1]);
+        // The last game is cut while being written, before its `end` line
+        let interrupted = &interrupted[..interrupted.len() - "end\n".len()];
+        let (_, games) = parse_session(interrupted).unwrap();
+        assert_eq!(games.len(), 1);
+        let (_, games) = parse_session(&(complete + "game 2\nx ha")).unwrap();
+        assert_eq!(games.len(), 1);
+    }
+
+    #[test]
+    fn invalid_session() {
+        let header = format_header(&session());
+        assert!(parse_session(&(header.clone() + &format_game(1, &games()[0]))).is_err());
+        let too_many = Session {
+            games: 1,
+            ..session()
+        };
+        let text =
+            format_header(&too_many) + &format_game(0, &games()[0]) + &format_game(1, &games()[1]);
+        assert!(parse_session(&text).is_err());
+        assert!(parse_session(&header.replace("seed 7\n", "")).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::ai::{AILevel, ScoreTable};
+use crate::morpion::Morpion;
+use crate::random::with_rng;
+
+/// Weakest skill of the [`AILevel::Skill`] levels.
+pub const MIN_SKILL: u8 = 1;
+/// Strongest skill of the [`AILevel::Skill`] levels.
+pub const MAX_SKILL: u8 = 20;
+/// Temperature of the softmax at the weakest skill, in score units.
+const MAX_TEMPERATURE: f64 = 50.0;
+/// Temperature of the softmax at the strongest skill: almost always the best move.
+const MIN_TEMPERATURE: f64 = 0.5;
+/// Probability of missing the opponent's threats at the weakest skill.
+const MAX_BLUNDER_RATE: f64 = 0.3;
+/// From this skill on, the AI uses its opening book.
+pub const BOOK_SKILL: u8 = 11;
+/// From this skill on, the AI plays the endgame perfectly when the solver can.
+pub const SOLVER_SKILL: u8 = 16;
+/// Skills rated along with the other agents, the menu shows the rating of the nearest one.
+pub const RATED_SKILLS: [u8; 5] = [1, 5, 10, 15, 20];
+
+/// Position of the skill between the weakest (`0.0`) and the strongest (`1.0`).
+fn strength(skill: u8) -> f64 {
+    (skill.clamp(MIN_SKILL, MAX_SKILL) - MIN_SKILL) as f64 / (MAX_SKILL - MIN_SKILL) as f64
+}
+
+/// Depth of the search after the move, from 0 (the replies are ignored) to 5 plies.
+pub fn skill_depth(skill: u8) -> isize {
+    (strength(skill) * 5.0).round() as isize
+}
+
+/// Temperature of the softmax choosing among the moves: the higher, the more random.
+pub fn skill_temperature(skill: u8) -> f64 {
+    MAX_TEMPERATURE * (MIN_TEMPERATURE / MAX_TEMPERATURE).powf(strength(skill))
+}
+
+/// Probability that the AI only looks at its own move and ignores the replies of the opponent.
+pub fn skill_blunder_rate(skill: u8) -> f64 {
+    MAX_BLUNDER_RATE * (1.0 - strength(skill))
+}
+
+/// Picks an index randomly, with probabilities following the softmax of the scores at the given temperature.
+fn softmax_pick(scores: &[isize], temperature: f64) -> usize {
+    let max = scores.iter().copied().max().unwrap_or(0);
+    let weights: Vec<f64> = scores
+        .iter()
+        .map(|score| ((score - max) as f64 / temperature).exp())
+        .collect();
+    let mut choice = with_rng(|rng| rng.random_range(0.0..weights.iter().sum::<f64>()));
+    for (index, weight) in weights.iter().enumerate() {
+        if choice < *weight {
+            return index;
+        }
+        choice -= weight;
+    }
+    scores.len() - 1
+}
+
+/// Chooses the move of the AI with the given skill: a softmax over the scores of the moves,
+/// which are sometimes computed without looking at the opponent's replies.
+/// The scores found are kept in `table`. Returns `None` if the search was aborted.
+pub fn skill_move(
+    morpion: &Morpion,
+    skill: u8,
+    table: &mut ScoreTable,
+    stop: &AtomicBool,
+) -> Option<(usize, usize)> {
+    if let Some((aided_move, _)) = morpion.aided_choice(AILevel::Skill(skill).aids(), stop) {
+        return Some(aided_move);
+    }
+    let blunder = with_rng(|rng| rng.random_bool(skill_blunder_rate(skill)));
+    // The weakest skill already ignores the replies
+    let level = if blunder {
+        AILevel::Skill(MIN_SKILL)
+    } else {
+        AILevel::Skill(skill)
+    };
+    let scores = morpion.score_moves(level, table, stop);
+    if stop.load(Ordering::Relaxed) || scores.is_empty() {
+        return None;
+    }
+    let values: Vec<isize> = scores.iter().map(|(_, score)| *score).collect();
+    Some(scores[softmax_pick(&values, skill_temperature(skill))].0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_spans_the_skills() {
+        assert!((skill_temperature(MIN_SKILL) - MAX_TEMPERATURE).abs() < 1e-9);
+        assert!((skill_temperature(MAX_SKILL) - MIN_TEMPERATURE).abs() < 1e-9);
+        assert!(skill_temperature(MIN_SKILL + 1) < skill_temperature(MIN_SKILL));
+    }
+
+    #[test]
+    fn softmax_follows_the_temperature() {
+        let scores = [10, 40, 0];
+        for _ in 0..100 {
+            assert_eq!(softmax_pick(&scores, MIN_TEMPERATURE), 1);
+        }
+        let picks: Vec<usize> = (0..1000)
+            .map(|_| softmax_pick(&scores, MAX_TEMPERATURE))
+            .collect();
+        assert!((0..scores.len()).all(|index| picks.contains(&index)));
+    }
+}
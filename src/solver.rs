@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::ai::generate_moves;
+use crate::morpion::{format_move, is_won_by, CellState, Morpion, PlayingState};
+
+/// Score of a position where the player to move has already lost.
+/// Proven scores are `±(MATE - distance)`, `0` is a proven tie.
+const MATE: isize = 1000;
+/// The solver takes over when there are at most this many free cells in the open big cells.
+pub const SOLVER_EMPTY_CELLS: usize = 16;
+/// ... or when there are at most this many open big cells.
+pub const SOLVER_OPEN_BOARDS: usize = 2;
+/// Maximum number of nodes visited by the solver before giving up.
+pub const SOLVER_MAX_NODES: usize = 2_000_000;
+/// Maximum number of entries in the transposition table.
+const TT_CAPACITY: usize = 1 << 20;
+/// Maximum number of positions remembered by [`endgame_solution`].
+const SOLUTIONS_CAPACITY: usize = 1 << 16;
+
+/// Results of [`endgame_solution`] by position hash, `None` for the positions that couldn't be solved.
+static SOLUTIONS: OnceLock<Mutex<HashMap<u64, Option<Solution>>>> = OnceLock::new();
+
+/// Result of the exact resolution of a position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    /// Proven result of the game: [`PlayingState::Win`] or [`PlayingState::Tie`].
+    pub outcome: PlayingState,
+    /// Number of moves (plies) before the win with perfect play (`0` for a tie).
+    pub distance: usize,
+    /// Best move `(ult_index, index)` for the player to move, `None` if the game is over.
+    pub best_move: Option<(usize, usize)>,
+}
+
+/// Bound stored in the transposition table, since alpha-beta scores are not always exact.
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// Exact solver: alpha-beta without depth limit, with a transposition table.
+struct Solver<'a> {
+    table: HashMap<u64, (isize, Bound)>,
+    nodes: usize,
+    max_nodes: usize,
+    stop: &'a AtomicBool,
+}
+
+/// Returns the number of free cells in the big cells that are still open.
+pub fn empty_cells(node: &Morpion) -> usize {
+    node.board
+        .states
+        .iter()
+        .zip(node.board.cells.iter())
+        .filter(|(state, _)| **state == CellState::Free)
+        .map(|(_, cells)| cells.iter().filter(|c| **c == CellState::Free).count())
+        .sum()
+}
+
+/// Tells if the position is close enough to the end of the game to be solved exactly.
+pub fn should_solve(node: &Morpion) -> bool {
+    let open_boards = node
+        .board
+        .states
+        .iter()
+        .filter(|state| **state == CellState::Free)
+        .count();
+    let empty = empty_cells(node);
+    empty <= SOLVER_EMPTY_CELLS
+        || (open_boards <= SOLVER_OPEN_BOARDS && empty <= 2 * SOLVER_EMPTY_CELLS)
+}
+
+/// Converts the score of a child into the score of its parent (one more move to play).
+fn parent_score(child_score: isize) -> isize {
+    let score = -child_score;
+    score - score.signum()
+}
+
+/// Inverse of [`parent_score`], used to pass the alpha-beta window down to the children.
+fn child_bound(parent_bound: isize) -> isize {
+    if parent_bound.abs() > MATE {
+        return -parent_bound;
+    }
+    -(parent_bound + parent_bound.signum())
+}
+
+impl<'a> Solver<'a> {
+    fn new(max_nodes: usize, stop: &'a AtomicBool) -> Self {
+        Self {
+            table: HashMap::new(),
+            nodes: 0,
+            max_nodes,
+            stop,
+        }
+    }
+
+    /// Returns the proven score of `node` from the point of view of the player to move,
+    /// or `None` if the node budget is exhausted or the search is stopped.
+    fn negamax(&mut self, node: &Morpion, mut alpha: isize, mut beta: isize) -> Option<isize> {
+        match node.state {
+            PlayingState::Win(_) => return Some(-MATE),
+            PlayingState::Tie => return Some(0),
+            PlayingState::Continue => {}
+        }
+        self.nodes += 1;
+        if self.nodes > self.max_nodes || self.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        let key = node.hash();
+        if let Some(&(score, bound)) = self.table.get(&key) {
+            match bound {
+                Bound::Exact => return Some(score),
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Upper => beta = beta.min(score),
+            }
+            if alpha >= beta {
+                return Some(score);
+            }
+        }
+        let original_alpha = alpha;
+        let mut best = -MATE - 1;
+        for (ult_index, index) in ordered_moves(node) {
+            let mut child = node.clone();
+            child.play_at(ult_index, index);
+            let child_score = self.negamax(&child, child_bound(beta), child_bound(alpha))?;
+            best = best.max(parent_score(child_score));
+            alpha = alpha.max(best);
+            if alpha >= beta {
+                break;
+            }
+        }
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        if self.table.len() >= TT_CAPACITY {
+            self.table.clear();
+        }
+        self.table.insert(key, (best, bound));
+        Some(best)
+    }
+}
+
+/// Orders the moves so that those winning a big cell are tried first.
+fn ordered_moves(node: &Morpion) -> Vec<(usize, usize)> {
+    let mut moves = generate_moves(node);
+    moves.sort_by_key(|&(ult_index, index)| {
+        let mut cells = node.board.cells[ult_index];
+        cells[index] = CellState::Occupied(node.player);
+        !is_won_by(&cells, node.player)
+    });
+    moves
+}
+
+/// Solves a position exactly, visiting at most `max_nodes` nodes.
+/// Returns `None` if the position could not be solved within the budget or if `stop` is set.
+pub fn solve(node: &Morpion, max_nodes: usize, stop: &AtomicBool) -> Option<Solution> {
+    match node.state {
+        PlayingState::Continue => {}
+        _ => {
+            return Some(Solution {
+                outcome: node.state.clone(),
+                distance: 0,
+                best_move: None,
+            })
+        }
+    }
+    let mut solver = Solver::new(max_nodes, stop);
+    let mut best: Option<(isize, (usize, usize))> = None;
+    for (ult_index, index) in ordered_moves(node) {
+        let mut child = node.clone();
+        child.play_at(ult_index, index);
+        let alpha = best.map_or(-MATE - 1, |(best_score, _)| best_score);
+        let score =
+            parent_score(solver.negamax(&child, child_bound(MATE + 1), child_bound(alpha))?);
+        if best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, (ult_index, index)));
+        }
+    }
+    let (score, best_move) = best?;
+    let distance = if score == 0 {
+        0
+    } else {
+        (MATE - score.abs()) as usize
+    };
+    Some(Solution {
+        outcome: match score.signum() {
+            1 => PlayingState::Win(node.player),
+            -1 => PlayingState::Win(node.player.other()),
+            _ => PlayingState::Tie,
+        },
+        distance,
+        best_move: Some(best_move),
+    })
+}
+
+/// Solves a position close to the end of the game (see [`should_solve`]) within [`SOLVER_MAX_NODES`] nodes.
+/// The results are remembered, so that a position is not searched again, even when it couldn't be solved.
+/// Returns `None` if the position is not close to the end, couldn't be solved or if `stop` is set.
+pub fn endgame_solution(node: &Morpion, stop: &AtomicBool) -> Option<Solution> {
+    if !should_solve(node) {
+        return None;
+    }
+    let solutions = SOLUTIONS.get_or_init(|| Mutex::new(HashMap::new()));
+    let key = node.hash();
+    if let Some(solution) = solutions.lock().unwrap().get(&key) {
+        return solution.clone();
+    }
+    let solution = solve(node, SOLVER_MAX_NODES, stop);
+    // A stopped search tells nothing about the position
+    if solution.is_some() || !stop.load(Ordering::Relaxed) {
+        let mut solutions = solutions.lock().unwrap();
+        if solutions.len() >= SOLUTIONS_CAPACITY {
+            solutions.clear();
+        }
+        solutions.insert(key, solution.clone());
+    }
+    solution
+}
+
+/// Solves the position given in notation (see [`Morpion::from_notation`]) and prints the result.
+pub fn launch_solve(notation: &str) -> Result<(), String> {
+    let morpion =
+        Morpion::from_notation(notation).map_err(|e| format!("invalid position: {}", e))?;
+    println!("{}\n{}", morpion, morpion.to_notation());
+    match solve(&morpion, usize::MAX, &AtomicBool::new(false)) {
+        Some(solution) => {
+            match solution.outcome {
+                PlayingState::Win(player) => {
+                    println!("=> {} wins in {} moves", player, solution.distance)
+                }
+                _ => println!("=> tie"),
+            }
+            if let Some(best_move) = solution.best_move {
+                println!("=> best move: {}", format_move(best_move));
+            }
+        }
+        None => println!("=> can't solve the position"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morpion::Player;
+
+    fn solve_notation(notation: &str) -> Option<Solution> {
+        let morpion = Morpion::from_notation(notation).unwrap();
+        solve(&morpion, usize::MAX, &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn finished_game() {
+        let solution = solve_notation(
+            "xxx....../xxx....../xxx....../........./........./........./........./........./......... o -",
+        );
+        assert_eq!(
+            solution,
+            Some(Solution {
+                outcome: PlayingState::Win(Player::X),
+                distance: 0,
+                best_move: None,
+            })
+        );
+    }
+
+    #[test]
+    fn win_in_one() {
+        let solution = solve_notation(
+            "xxx....../xxx....../xx......./........./........./........./........./........./......... x 2",
+        );
+        assert_eq!(
+            solution,
+            Some(Solution {
+                outcome: PlayingState::Win(Player::X),
+                distance: 1,
+                best_move: Some((2, 2)),
+            })
+        );
+    }
+
+    #[test]
+    fn forced_loss() {
+        // O's only move sends X to the big cell it wins the game in
+        let solution = solve_notation(
+            "xxx....../xxx....../xx......./xo.oxxoxo/........./........./........./........./......... o 3",
+        );
+        assert_eq!(
+            solution,
+            Some(Solution {
+                outcome: PlayingState::Win(Player::X),
+                distance: 2,
+                best_move: Some((3, 2)),
+            })
+        );
+    }
+
+    #[test]
+    fn gives_up() {
+        let morpion = Morpion::new();
+        assert_eq!(solve(&morpion, 100, &AtomicBool::new(false)), None);
+        assert_eq!(solve(&morpion, usize::MAX, &AtomicBool::new(true)), None);
+    }
+}
@@ -0,0 +1,367 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::agent::{AgentError, AgentSpec};
+use crate::fight::{fight_all, Game, Pairing};
+use crate::morpion::{Player, PlayingState};
+use crate::rating::{ELO_SCALE, Z_95};
+
+/// Wins, draws and losses of an agent in a match.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl Score {
+    /// Records the result of a game where the agent played `player`.
+    pub fn add(&mut self, result: &PlayingState, player: Player) {
+        match result {
+            PlayingState::Win(winner) if *winner == player => self.wins += 1,
+            PlayingState::Win(_) => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Average points per game: 1 per win, 0.5 per draw.
+    pub fn mean(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Variance of the points of a single game.
+    fn variance(&self) -> f64 {
+        let mean = self.mean();
+        (self.wins as f64 * (1.0 - mean).powi(2)
+            + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2))
+            / self.games() as f64
+    }
+
+    /// Variance of the points of a single game, as if one more game had been won and one more lost,
+    /// so that it isn't zero while every game has the same result.
+    fn regularised_variance(&self) -> f64 {
+        Score {
+            wins: self.wins + 1,
+            draws: self.draws,
+            losses: self.losses + 1,
+        }
+        .variance()
+    }
+
+    /// Estimates the Elo difference with the opponent, with its 95% confidence interval.
+    /// Returns `None` if no game was played.
+    pub fn elo(&self) -> Option<EloDifference> {
+        if self.games() == 0 {
+            return None;
+        }
+        let mean = self.mean();
+        let error = Z_95 * (self.variance() / self.games() as f64).sqrt();
+        Some(EloDifference {
+            elo: score_to_elo(mean),
+            low: score_to_elo((mean - error).max(0.0)),
+            high: score_to_elo((mean + error).min(1.0)),
+        })
+    }
+
+    /// Likelihood of superiority: probability that the agent is stronger than its opponent.
+    /// Draws don't tell which one is stronger, so they are ignored.
+    pub fn los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt()))
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// Thinking time of an agent over a series of games.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ThinkingTime {
+    pub moves: usize,
+    pub total: Duration,
+    pub longest: Duration,
+    /// Games lost on time.
+    pub time_forfeits: usize,
+}
+
+impl ThinkingTime {
+    /// Records the moves of a game where the agent played `player`.
+    pub fn add(&mut self, game: &Game, player: Player) {
+        for &time in game.times(player) {
+            self.moves += 1;
+            self.total += time;
+            self.longest = self.longest.max(time);
+        }
+        if let Some((loser, AgentError::Timeout(_))) = &game.forfeit {
+            if *loser == player {
+                self.time_forfeits += 1;
+            }
+        }
+    }
+
+    /// Average thinking time per move, zero if no move was played.
+    pub fn average(&self) -> Duration {
+        self.total
+            .checked_div(self.moves as u32)
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for ThinkingTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1} ms/move, longest {:.1} ms, total {:.1} s, {} time forfeits",
+            self.average().as_secs_f64() * 1000.0,
+            self.longest.as_secs_f64() * 1000.0,
+            self.total.as_secs_f64(),
+            self.time_forfeits
+        )
+    }
+}
+
+/// Elo difference between two agents, with its 95% confidence interval.
+#[derive(Clone, Copy, Debug)]
+pub struct EloDifference {
+    pub elo: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+impl std::fmt::Display for EloDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:+.0} [{:+.0}, {:+.0}]", self.elo, self.low, self.high)
+    }
+}
+
+/// Converts an average score into an Elo difference, infinite for a score of 0 or 1.
+fn score_to_elo(score: f64) -> f64 {
+    ELO_SCALE * (score / (1.0 - score)).ln()
+}
+
+/// Expected average score of an agent stronger by `elo` points.
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + (-elo / ELO_SCALE).exp())
+}
+
+/// Error function, with the approximation 7.1.26 of _Abramowitz and Stegun_ (error below 1.5e-7).
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+/// Outcome of a [`Sprt`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hypothesis {
+    /// The agent is stronger than its opponent by at most `elo0` points.
+    H0,
+    /// The agent is stronger than its opponent by at least `elo1` points.
+    H1,
+}
+
+/// _Sequential probability ratio test_ between two hypotheses on the Elo difference of two agents:
+/// it is at most `elo0` (H0) or at least `elo1` (H1), `alpha` and `beta` being the probabilities of wrongly
+/// accepting H1 and H0.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    /// Log-likelihood ratio of H1 against H0, with a normal approximation of the results.
+    pub fn llr(&self, score: &Score) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let (score0, score1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+        score.games() as f64 * (score1 - score0) * (2.0 * score.mean() - score0 - score1)
+            / (2.0 * score.regularised_variance())
+    }
+
+    /// Bounds of the log-likelihood ratio under which H0 is accepted, and over which H1 is accepted.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Returns the accepted hypothesis, or `None` if more games are needed.
+    pub fn result(&self, score: &Score) -> Option<Hypothesis> {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            Some(Hypothesis::H0)
+        } else if llr >= upper {
+            Some(Hypothesis::H1)
+        } else {
+            None
+        }
+    }
+}
+
+/// Plays games between `a` and `b` on `threads` threads, colours alternating, until the [`Sprt`]
+/// accepts a hypothesis or `max_games` games are played, then prints the result.
+pub fn launch_sprt(a: AgentSpec, b: AgentSpec, sprt: Sprt, max_games: usize, threads: usize) {
+    let (lower, upper) = sprt.bounds();
+    let mut score = Score::default();
+    let mut result = None;
+    // Games are played in batches of one pair per thread, the results after the decision are ignored
+    while result.is_none() && score.games() < max_games {
+        let batch = (2 * threads.max(1)).min(max_games - score.games());
+        let first = score.games();
+        let pairings: Vec<Pairing> = (first..first + batch)
+            .map(|game| {
+                if game % 2 == 0 {
+                    Pairing::new(a, b)
+                } else {
+                    Pairing::new(b, a)
+                }
+            })
+            .collect();
+        fight_all(&pairings, rand::random(), threads, |index, game| {
+            if result.is_some() {
+                return;
+            }
+            let player = if (first + index) % 2 == 0 {
+                Player::X
+            } else {
+                Player::O
+            };
+            score.add(&game.result, player);
+            result = sprt.result(&score);
+            print!(
+                "\r{} games, {} {}, LLR {:.2} ({:.2}, {:.2})   ",
+                score.games(),
+                a.name(),
+                score,
+                sprt.llr(&score),
+                lower,
+                upper
+            );
+            let _ = io::stdout().flush();
+        });
+    }
+    println!();
+    println!(
+        "-- SPRT {} vs {} (elo0 {}, elo1 {}, alpha {}, beta {}) --",
+        a.name(),
+        b.name(),
+        sprt.elo0,
+        sprt.elo1,
+        sprt.alpha,
+        sprt.beta
+    );
+    println!("=> {}: {} ({} games)", a.name(), score, score.games());
+    print_elo(&score);
+    match result {
+        Some(Hypothesis::H0) => println!(
+            "=> H0 accepted: {} is at most {} Elo stronger",
+            a.name(),
+            sprt.elo0
+        ),
+        Some(Hypothesis::H1) => println!(
+            "=> H1 accepted: {} is at least {} Elo stronger",
+            a.name(),
+            sprt.elo1
+        ),
+        None => println!("=> inconclusive after {} games", score.games()),
+    }
+}
+
+/// Prints the Elo difference of an agent with its opponent and its likelihood of superiority.
+pub fn print_elo(score: &Score) {
+    if let Some(elo) = score.elo() {
+        println!("=> Elo difference: {} (95% confidence)", elo);
+    }
+    println!("=> LOS: {:.1}%", score.los() * 100.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(wins: usize, draws: usize, losses: usize) -> Score {
+        Score {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    const SPRT: Sprt = Sprt {
+        elo0: 0.0,
+        elo1: 10.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+
+    #[test]
+    fn erf_values() {
+        assert!(erf(0.0).abs() < 1e-6);
+        assert!((erf(1.0) - 0.842_700_79).abs() < 1e-6);
+        assert!((erf(-1.0) + 0.842_700_79).abs() < 1e-6);
+        assert!((erf(3.0) - 0.999_977_91).abs() < 1e-6);
+    }
+
+    #[test]
+    fn elo_and_score() {
+        for elo in [-400.0, -50.0, 0.0, 120.0] {
+            assert!((score_to_elo(elo_to_score(elo)) - elo).abs() < 1e-9);
+        }
+        let even = score(10, 5, 10).elo().unwrap();
+        assert!(even.elo.abs() < 1e-9 && even.low < 0.0 && even.high > 0.0);
+        let better = score(30, 10, 10).elo().unwrap();
+        assert!(better.low > 0.0 && better.low < better.elo && better.elo < better.high);
+        assert!(Score::default().elo().is_none());
+    }
+
+    #[test]
+    fn los() {
+        assert_eq!(score(0, 7, 0).los(), 0.5);
+        assert!((score(12, 3, 12).los() - 0.5).abs() < 1e-9);
+        // 0.5 * (1 + erf(10 / sqrt(20)))
+        assert!((score(10, 0, 0).los() - 0.999_217).abs() < 1e-6);
+        assert!((score(4, 0, 10).los() + score(10, 0, 4).los() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sprt_bounds() {
+        let (lower, upper) = SPRT.bounds();
+        assert!((lower + 2.944_439).abs() < 1e-6);
+        assert!((upper - 2.944_439).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sprt_decides_one_sided_runs() {
+        assert_eq!(SPRT.llr(&Score::default()), 0.0);
+        assert_eq!(SPRT.result(&score(1, 0, 0)), None);
+        assert_eq!(SPRT.result(&score(100, 0, 0)), Some(Hypothesis::H1));
+        assert_eq!(SPRT.result(&score(0, 0, 100)), Some(Hypothesis::H0));
+        assert_eq!(SPRT.result(&score(0, 2000, 0)), Some(Hypothesis::H0));
+    }
+
+    #[test]
+    fn sprt_follows_the_score() {
+        assert!(SPRT.llr(&score(60, 20, 40)) > SPRT.llr(&score(50, 20, 50)));
+        assert_eq!(SPRT.result(&score(600, 200, 400)), Some(Hypothesis::H1));
+        assert_eq!(SPRT.result(&score(5000, 2000, 5000)), Some(Hypothesis::H0));
+    }
+}
@@ -0,0 +1,183 @@
+use std::fs;
+use std::path::Path;
+
+use crate::agent::AgentSpec;
+use crate::fight::{fight_all, Pairing};
+use crate::morpion::{Player, PlayingState};
+
+/// Order in which the agents meet in a tournament.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schedule {
+    /// Every agent meets every other agent.
+    RoundRobin,
+    /// The first agent meets every other agent, which don't meet each other.
+    Gauntlet,
+}
+
+impl Schedule {
+    /// Converts a string into a [`Schedule`].
+    /// Returns `None` if the input string does not match any schedule.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "round-robin" => Some(Schedule::RoundRobin),
+            "gauntlet" => Some(Schedule::Gauntlet),
+            _ => None,
+        }
+    }
+
+    /// Returns the pairings `(i, j)` of agents meeting each other, `i < j`.
+    fn pairings(&self, n: usize) -> Vec<(usize, usize)> {
+        match self {
+            Schedule::RoundRobin => (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .collect(),
+            Schedule::Gauntlet => (1..n).map(|j| (0, j)).collect(),
+        }
+    }
+
+    /// Returns the games `(x, o)` of a tournament between `n` agents, `games` per pairing.
+    /// The agents of a pairing play `X` in turn.
+    pub fn games(&self, n: usize, games: usize) -> Vec<(usize, usize)> {
+        self.pairings(n)
+            .into_iter()
+            .flat_map(|(i, j)| {
+                (0..games).map(move |game| if game % 2 == 0 { (i, j) } else { (j, i) })
+            })
+            .collect()
+    }
+}
+
+/// Results of a tournament: wins, draws and losses of every agent against every other one.
+pub struct Crosstable {
+    agents: Vec<AgentSpec>,
+    /// `wins[i][j]` is the number of games won by `i` against `j`.
+    wins: Vec<Vec<usize>>,
+    /// `draws[i][j]` is the number of ties between `i` and `j`.
+    draws: Vec<Vec<usize>>,
+}
+
+impl Crosstable {
+    pub fn new(agents: &[AgentSpec]) -> Self {
+        let n = agents.len();
+        Self {
+            agents: agents.to_vec(),
+            wins: vec![vec![0; n]; n],
+            draws: vec![vec![0; n]; n],
+        }
+    }
+
+    /// Records a game between `x` and `o`.
+    pub fn add(&mut self, x: usize, o: usize, result: &PlayingState) {
+        match result {
+            PlayingState::Win(Player::X) => self.wins[x][o] += 1,
+            PlayingState::Win(Player::O) => self.wins[o][x] += 1,
+            _ => {
+                self.draws[x][o] += 1;
+                self.draws[o][x] += 1;
+            }
+        }
+    }
+
+    /// Number of games played between `i` and `j`.
+    fn games(&self, i: usize, j: usize) -> usize {
+        self.wins[i][j] + self.draws[i][j] + self.wins[j][i]
+    }
+
+    /// Points scored by `i`: 1 per win, 0.5 per tie.
+    pub fn points(&self, i: usize) -> f64 {
+        self.wins[i].iter().sum::<usize>() as f64 + self.draws[i].iter().sum::<usize>() as f64 / 2.0
+    }
+
+    /// Returns the indices of the agents, from the most points to the fewest.
+    pub fn ranking(&self) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.agents.len()).collect();
+        ranking.sort_by(|&i, &j| self.points(j).total_cmp(&self.points(i)));
+        ranking
+    }
+}
+
+impl std::fmt::Display for Crosstable {
+    /// Writes one line per agent from the best to the worst, with its points, its games and
+    /// its wins-draws-losses against every other agent, in the same order.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranking = self.ranking();
+        let names: Vec<String> = self.agents.iter().map(|agent| agent.name()).collect();
+        let width = names
+            .iter()
+            .map(String::len)
+            .max()
+            .unwrap_or(0)
+            .max("agent".len());
+        let cell = |i: usize, j: usize| {
+            if i == j {
+                "-".to_string()
+            } else if self.games(i, j) == 0 {
+                String::new()
+            } else {
+                format!(
+                    "{}-{}-{}",
+                    self.wins[i][j], self.draws[i][j], self.wins[j][i]
+                )
+            }
+        };
+        let cell_width = (0..self.agents.len())
+            .flat_map(|i| (0..self.agents.len()).map(move |j| (i, j)))
+            .map(|(i, j)| cell(i, j).len())
+            .max()
+            .unwrap_or(0)
+            .max(3);
+        write!(
+            f,
+            "{:<4} {:<width$} {:>7} {:>5}",
+            "rank", "agent", "points", "games"
+        )?;
+        for rank in 1..=ranking.len() {
+            write!(f, " {:>cell_width$}", rank)?;
+        }
+        writeln!(f)?;
+        for (rank, &i) in ranking.iter().enumerate() {
+            let games: usize = (0..self.agents.len()).map(|j| self.games(i, j)).sum();
+            write!(
+                f,
+                "{:<4} {:<width$} {:>7.1} {:>5}",
+                rank + 1,
+                names[i],
+                self.points(i),
+                games
+            )?;
+            for &j in &ranking {
+                write!(f, " {:>cell_width$}", cell(i, j))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plays a tournament between the agents on `threads` threads, `games` games per pairing,
+/// then prints the crosstable and writes it to `output`.
+pub fn launch_tournament(
+    schedule: Schedule,
+    agents: &[AgentSpec],
+    games: usize,
+    threads: usize,
+    output: &Path,
+) -> Result<(), String> {
+    let indices = schedule.games(agents.len(), games);
+    let pairings: Vec<Pairing> = indices
+        .iter()
+        .map(|&(x, o)| Pairing::new(agents[x], agents[o]))
+        .collect();
+    let mut crosstable = Crosstable::new(agents);
+    fight_all(&pairings, rand::random(), threads, |index, game| {
+        let (x, o) = indices[index];
+        println!("game {}/{}: {}", index + 1, indices.len(), game);
+        crosstable.add(x, o, &game.result);
+    });
+    println!(
+        "-- {:?} tournament ({} games per pairing) --\n{}",
+        schedule, games, crosstable
+    );
+    fs::write(output, crosstable.to_string())
+        .map_err(|e| format!("can't write {}: {}", output.display(), e))
+}
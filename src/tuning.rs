@@ -0,0 +1,263 @@
+use rand::Rng;
+use std::path::Path;
+
+use crate::ai::{
+    center_heuristic_with, corner_heuristic_with, everywhere_heuristic_with, generate_children,
+    params, threat_heuristic_with, AILevel, HeuristicParams,
+};
+use crate::fight::Game;
+use crate::gamestats::load_games;
+use crate::morpion::{Morpion, Player, PlayingState};
+
+/// Number of random moves played at the beginning of each self-play game, so games differ.
+const RANDOM_PLIES: usize = 4;
+/// Maximum number of passes over all the parameters during the local search.
+const MAX_PASSES: usize = 50;
+/// Smallest logistic scale tried, below which the heuristic no longer predicts anything.
+const MIN_SCALE: f64 = 1e-6;
+
+/// A position reached during self-play, labelled with the final result of its game
+/// from `X`'s point of view (`1.0` for a win, `0.5` for a tie, `0.0` for a loss).
+pub struct Sample {
+    pub morpion: Morpion,
+    pub result: f64,
+}
+
+/// A heuristic whose parameters can be tuned.
+#[derive(Clone, Copy, Debug)]
+pub enum Tunable {
+    Center,
+    Corner,
+    Everywhere,
+    Threat,
+}
+
+impl Tunable {
+    /// Converts the name of a heuristic into a [`Tunable`].
+    /// Returns `None` if the input string does not match any tunable heuristic.
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "center" => Some(Tunable::Center),
+            "corner" => Some(Tunable::Corner),
+            "everywhere" => Some(Tunable::Everywhere),
+            "threat" => Some(Tunable::Threat),
+            _ => None,
+        }
+    }
+
+    /// Returns the heuristic function, taking its parameters as argument.
+    fn heuristic(&self) -> fn(&Morpion, Player, &HeuristicParams) -> isize {
+        match self {
+            Tunable::Center => center_heuristic_with,
+            Tunable::Corner => corner_heuristic_with,
+            Tunable::Everywhere => everywhere_heuristic_with,
+            Tunable::Threat => threat_heuristic_with,
+        }
+    }
+
+    /// Returns the AI level searching with the heuristic, which plays the self-play games.
+    fn level(&self) -> AILevel {
+        match self {
+            Tunable::Center => AILevel::Medium,
+            Tunable::Corner => AILevel::Easy,
+            Tunable::Everywhere => AILevel::Hard,
+            Tunable::Threat => AILevel::Threat,
+        }
+    }
+
+    /// Returns the names of the parameters used by the heuristic.
+    fn parameters(&self) -> &'static [&'static str] {
+        match self {
+            Tunable::Center => &["weights_center", "big_cell_factor"],
+            Tunable::Corner => &["weights_corner", "big_cell_factor"],
+            Tunable::Everywhere => &[
+                "meta_sequence_factor",
+                "won_big_cell",
+                "won_center_big_cell",
+                "won_corner_big_cell",
+                "center_cell",
+                "center_board_cell",
+                "free_choice",
+            ],
+            Tunable::Threat => &[
+                "local_threat",
+                "meta_threat",
+                "threat_big_cell",
+                "sent_to_threat",
+                "sent_to_free",
+            ],
+        }
+    }
+}
+
+/// Plays `games` AI vs AI games at the given level and collects every position reached.
+/// The first moves of each game are random to get a variety of positions.
+pub fn self_play(games: usize, level: AILevel) -> Vec<Sample> {
+    let mut rng = rand::rng();
+    let mut samples = Vec::new();
+    for game in 0..games {
+        let mut positions = Vec::new();
+        let mut morpion = Morpion::new();
+        while !morpion.is_over() {
+            positions.push(morpion.clone());
+            morpion = if positions.len() <= RANDOM_PLIES {
+                let mut children = generate_children(&morpion);
+                children.swap_remove(rng.random_range(0..children.len()))
+            } else {
+                morpion.ai_move(level)
+            };
+        }
+        let result = x_result(&morpion.state);
+        println!("self-play game {}/{}: {:?}", game + 1, games, morpion.state);
+        samples.extend(
+            positions
+                .into_iter()
+                .skip(RANDOM_PLIES)
+                .map(|morpion| Sample { morpion, result }),
+        );
+    }
+    samples
+}
+
+/// Returns the result of a finished game from `X`'s point of view, as labelled in the samples.
+fn x_result(state: &PlayingState) -> f64 {
+    match state {
+        PlayingState::Win(Player::X) => 1.0,
+        PlayingState::Win(Player::O) => 0.0,
+        _ => 0.5,
+    }
+}
+
+/// Collects the positions reached in recorded games, replaying their moves.
+/// The games lost by forfeit are left out, their result doesn't come from the positions.
+pub fn recorded_samples(games: &[Game]) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    for game in games.iter().filter(|game| game.forfeit.is_none()) {
+        let result = x_result(&game.result);
+        let mut morpion = game.start_position();
+        for &(ult_index, index) in &game.moves {
+            samples.push(Sample {
+                morpion: morpion.clone(),
+                result,
+            });
+            morpion.play_at(ult_index, index);
+        }
+    }
+    samples
+}
+
+/// Maps a heuristic score to an expected result with a logistic curve of scale `k`.
+fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + (-k * score).exp())
+}
+
+/// Computes the mean squared error between the game results and the predictions of the heuristic.
+fn mean_error(samples: &[Sample], tunable: Tunable, params: &HeuristicParams, k: f64) -> f64 {
+    let heuristic = tunable.heuristic();
+    let total: f64 = samples
+        .iter()
+        .map(|sample| {
+            let score = heuristic(&sample.morpion, Player::X, params) as f64;
+            (sample.result - sigmoid(score, k)).powi(2)
+        })
+        .sum();
+    total / samples.len() as f64
+}
+
+/// Finds the logistic scale that best fits the current parameters,
+/// so the local search only has to move the parameters relative to each other.
+fn fit_scale(samples: &[Sample], tunable: Tunable, params: &HeuristicParams) -> f64 {
+    let mut best_k = 1.0;
+    let mut best_error = mean_error(samples, tunable, params, best_k);
+    let mut step = 10.0;
+    while step > 1.0001 {
+        let mut improved = false;
+        for k in [best_k * step, (best_k / step).max(MIN_SCALE)] {
+            let error = mean_error(samples, tunable, params, k);
+            if error < best_error {
+                best_error = error;
+                best_k = k;
+                improved = true;
+            }
+        }
+        if !improved {
+            step = step.sqrt();
+        }
+    }
+    best_k
+}
+
+/// Optimises the parameters of a heuristic with a _Texel-style_ logistic fitting:
+/// each parameter is moved up and down as long as it reduces the prediction error on the samples.
+/// The logistic scale is fitted again after each pass, as the parameters move it.
+pub fn tune(samples: &[Sample], tunable: Tunable, initial: &HeuristicParams) -> HeuristicParams {
+    let mut k = fit_scale(samples, tunable, initial);
+    let mut params = initial.clone();
+    let mut values = params.values();
+    let indices: Vec<usize> = tunable
+        .parameters()
+        .iter()
+        .flat_map(|name| params.range_of(name))
+        .collect();
+    let mut best_error = mean_error(samples, tunable, &params, k);
+    println!("initial error: {:.6} (k = {:e})", best_error, k);
+
+    for pass in 0..MAX_PASSES {
+        let mut improved = false;
+        for &index in &indices {
+            let step = (values[index].abs() / 8).max(1);
+            for delta in [step, -step] {
+                let mut candidate = values.clone();
+                candidate[index] += delta;
+                params.set_values(&candidate);
+                let error = mean_error(samples, tunable, &params, k);
+                if error < best_error {
+                    best_error = error;
+                    values = candidate;
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        params.set_values(&values);
+        k = fit_scale(samples, tunable, &params);
+        best_error = mean_error(samples, tunable, &params, k);
+        println!("pass {}: error {:.6} (k = {:e})", pass + 1, best_error, k);
+        if !improved {
+            break;
+        }
+    }
+    params
+}
+
+/// Launches a tuning session.
+/// Generates `games` self-play games with the level using the heuristic, adds the positions of the games
+/// of `records` (a fight session file or a directory of game records) if given,
+/// tunes the parameters of the heuristic and writes them to `output`.
+pub fn launch_tuning(
+    tunable: Tunable,
+    games: usize,
+    records: Option<&Path>,
+    output: &Path,
+) -> Result<(), String> {
+    let mut samples = self_play(games, tunable.level());
+    if let Some(records) = records {
+        let games =
+            load_games(records).map_err(|e| format!("can't read {}: {}", records.display(), e))?;
+        samples.extend(recorded_samples(&games));
+    }
+    if samples.is_empty() {
+        return Err(String::from("no positions to tune on"));
+    }
+    println!("-- {} positions collected --", samples.len());
+    let tuned = tune(&samples, tunable, params());
+    tuned
+        .save(output)
+        .map_err(|e| format!("can't write {}: {}", output.display(), e))?;
+    println!(
+        "-- tuned parameters written to {} --\n{}",
+        output.display(),
+        tuned
+    );
+    Ok(())
+}
@@ -0,0 +1,334 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::agent::{agent_move, Agent, AgentError, AgentSpec};
+use crate::ai::{everywhere_heuristic, generate_moves, AILevel, Evaluation, ScoreTable};
+use crate::morpion::Morpion;
+
+/// What the worker has to do with a position.
+#[derive(Clone, Copy, PartialEq)]
+enum JobKind {
+    /// Compute the move of the agent for the position.
+    Search(AgentSpec),
+    /// The opponent is thinking: compute the AI replies to their likely moves in advance.
+    /// The level keeps the scores found for its search once the opponent has moved.
+    Ponder(AILevel),
+    /// Evaluate every move of the position, e.g. to give a hint.
+    Analyse(AILevel),
+    /// Evaluate the position itself, e.g. for the evaluation bar.
+    Evaluate(AILevel),
+}
+
+/// A search requested to the worker.
+struct Job {
+    id: usize,
+    kind: JobKind,
+    morpion: Morpion,
+    stop: Arc<AtomicBool>,
+}
+
+/// Messages sent back by the worker thread.
+enum Message {
+    /// The job is over, with the result of a search (`None` if it was aborted or for the other jobs).
+    Done(usize, Option<Result<Morpion, AgentError>>),
+    /// While pondering, the AI reply to the position with the given hash has been computed.
+    Pondered(usize, u64, Morpion),
+    /// The moves of the analysed position with their score, best first.
+    Analysis(usize, Vec<((usize, usize), isize)>),
+    /// The evaluation of the position, or the one the agent gave with its move.
+    Evaluation(usize, Evaluation),
+}
+
+/// Persistent background thread computing AI moves, so the UI never freezes.
+/// Searches can be cancelled: they check a stop flag and return as soon as it is set.
+pub struct AIWorker {
+    jobs: Option<Sender<Job>>,
+    messages: Receiver<Message>,
+    thread: Option<JoinHandle<()>>,
+    /// Id, kind and stop flag of the running job, if any.
+    current: Option<(usize, JobKind, Arc<AtomicBool>)>,
+    next_id: usize,
+    /// Hash of the position pondered on, kept once the pondering is over.
+    pondered_position: Option<u64>,
+    /// AI replies computed while pondering, by hash of the position they answer.
+    pondered: HashMap<u64, Morpion>,
+    /// Result of the last analysis, until it is taken.
+    analysis: Option<Vec<((usize, usize), isize)>>,
+    /// Result of the last evaluation, until it is taken.
+    evaluation: Option<Evaluation>,
+}
+
+/// Computes the move of the agent with its evaluation if it gave one, or `None` if the search was aborted.
+/// Agents are built at their first move and kept in `agents`: the AI levels keep the scores they found,
+/// external engines keep running.
+fn search(
+    agents: &mut HashMap<AgentSpec, Box<dyn Agent>>,
+    spec: AgentSpec,
+    morpion: &Morpion,
+    stop: &AtomicBool,
+) -> Option<Result<(Morpion, Option<Evaluation>), AgentError>> {
+    let agent = match agents.entry(spec) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => match spec.build() {
+            Ok(agent) => entry.insert(agent),
+            Err(e) => return Some(Err(e)),
+        },
+    };
+    let result = agent_move(agent.as_mut(), morpion, stop);
+    let evaluation = agent.evaluation();
+    if result.is_err() {
+        // The engine is in an unknown state, it is restarted for its next game
+        agents.remove(&spec);
+    }
+    result
+        .map(|new_state| new_state.map(|new_state| (new_state, evaluation)))
+        .transpose()
+}
+
+/// Returns the positions the player to move is the most likely to reach, best first.
+fn likely_children(morpion: &Morpion) -> Vec<Morpion> {
+    let mut children: Vec<Morpion> = generate_moves(morpion)
+        .into_iter()
+        .map(|(ult_index, index)| {
+            let mut child = morpion.clone();
+            child.play_at(ult_index, index);
+            child
+        })
+        .collect();
+    children.sort_by_key(|child| -everywhere_heuristic(child, morpion.player));
+    children
+}
+
+impl AIWorker {
+    /// Spawns the worker thread, which waits for searches until the worker is shut down.
+    pub fn new() -> Self {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (message_sender, messages) = channel();
+        let thread = thread::spawn(move || {
+            let mut agents = HashMap::new();
+            for job in job_receiver {
+                let new_state = match job.kind {
+                    JobKind::Search(spec) => {
+                        let result = search(&mut agents, spec, &job.morpion, &job.stop);
+                        if let Some(Ok((_, Some(evaluation)))) = &result {
+                            let message = Message::Evaluation(job.id, evaluation.clone());
+                            if message_sender.send(message).is_err() {
+                                return;
+                            }
+                        }
+                        result.map(|result| result.map(|(new_state, _)| new_state))
+                    }
+                    JobKind::Ponder(level) => {
+                        let spec = AgentSpec::Level(level);
+                        for child in likely_children(&job.morpion) {
+                            let Some(Ok((reply, _))) = search(&mut agents, spec, &child, &job.stop)
+                            else {
+                                break;
+                            };
+                            let message = Message::Pondered(job.id, child.hash(), reply);
+                            if message_sender.send(message).is_err() {
+                                return;
+                            }
+                        }
+                        None
+                    }
+                    JobKind::Analyse(level) => {
+                        let mut scores =
+                            job.morpion
+                                .score_moves(level, &mut ScoreTable::new(), &job.stop);
+                        if !job.stop.load(Ordering::Relaxed) {
+                            scores.sort_by_key(|(_, score)| -score);
+                            if message_sender
+                                .send(Message::Analysis(job.id, scores))
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                        None
+                    }
+                    JobKind::Evaluate(level) => {
+                        if let Some(evaluation) = job.morpion.evaluate(level, &job.stop) {
+                            if message_sender
+                                .send(Message::Evaluation(job.id, evaluation))
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                        None
+                    }
+                };
+                if message_sender
+                    .send(Message::Done(job.id, new_state))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Self {
+            jobs: Some(jobs),
+            messages,
+            thread: Some(thread),
+            current: None,
+            next_id: 0,
+            pondered_position: None,
+            pondered: HashMap::new(),
+            analysis: None,
+            evaluation: None,
+        }
+    }
+
+    /// Tells if a search (not pondering) is running.
+    pub fn is_busy(&self) -> bool {
+        matches!(self.current, Some((_, JobKind::Search(_), _)))
+    }
+
+    /// Sends a job to the worker thread, cancelling the running one if any.
+    fn send(&mut self, kind: JobKind, morpion: Morpion) {
+        self.cancel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let job = Job {
+            id: self.next_id,
+            kind,
+            morpion,
+            stop: stop.clone(),
+        };
+        if let Some(jobs) = &self.jobs {
+            if jobs.send(job).is_ok() {
+                self.current = Some((self.next_id, kind, stop));
+            }
+        }
+        self.next_id += 1;
+    }
+
+    /// Starts searching the move of the agent for the given state, cancelling the running search if any.
+    pub fn start(&mut self, morpion: Morpion, agent: AgentSpec) {
+        self.clear_pondered();
+        self.evaluation = None;
+        self.send(JobKind::Search(agent), morpion);
+    }
+
+    /// Starts pondering on the given state, where the opponent of the AI is to move.
+    /// Does nothing if the worker is already pondering (or has pondered) on this state.
+    pub fn ponder(&mut self, morpion: Morpion, level: AILevel) {
+        let hash = morpion.hash();
+        if self.pondered_position == Some(hash) {
+            return;
+        }
+        self.clear_pondered();
+        self.send(JobKind::Ponder(level), morpion);
+        self.pondered_position = Some(hash);
+    }
+
+    /// Starts evaluating every move of the given state with the search of the given level.
+    pub fn analyse(&mut self, morpion: Morpion, level: AILevel) {
+        self.analysis = None;
+        self.send(JobKind::Analyse(level), morpion);
+    }
+
+    /// Returns the moves with their score (best first) once the analysis is finished.
+    pub fn try_analysis(&mut self) -> Option<Vec<((usize, usize), isize)>> {
+        self.try_result();
+        self.analysis.take()
+    }
+
+    /// Starts evaluating the given state with the search of the given level.
+    pub fn evaluate(&mut self, morpion: Morpion, level: AILevel) {
+        self.evaluation = None;
+        self.send(JobKind::Evaluate(level), morpion);
+    }
+
+    /// Returns the evaluation once it is finished, or the one the agent gave with the move it just found.
+    pub fn try_evaluation(&mut self) -> Option<Evaluation> {
+        self.try_result();
+        self.evaluation.take()
+    }
+
+    /// Forgets the replies computed while pondering.
+    fn clear_pondered(&mut self) {
+        self.pondered_position = None;
+        self.pondered.clear();
+    }
+
+    /// Handles a message from the worker thread.
+    /// Returns the message's result if it ends the running search.
+    fn handle(&mut self, message: Message) -> Option<Result<Morpion, AgentError>> {
+        let current_id = self.current.as_ref().map(|(id, _, _)| *id);
+        match message {
+            Message::Pondered(id, hash, reply) if Some(id) == current_id => {
+                self.pondered.insert(hash, reply);
+                None
+            }
+            Message::Analysis(id, scores) if Some(id) == current_id => {
+                self.analysis = Some(scores);
+                None
+            }
+            Message::Evaluation(id, evaluation) if Some(id) == current_id => {
+                self.evaluation = Some(evaluation);
+                None
+            }
+            Message::Done(id, new_state) if Some(id) == current_id => {
+                self.current = None;
+                new_state
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the result of the running search if it is finished:
+    /// the new state, or the reason why the agent lost.
+    pub fn try_result(&mut self) -> Option<Result<Morpion, AgentError>> {
+        while let Ok(message) = self.messages.try_recv() {
+            if let Some(new_state) = self.handle(message) {
+                return Some(new_state);
+            }
+        }
+        None
+    }
+
+    /// Returns the AI reply to the given state if it was computed while pondering.
+    /// Pondering is then stopped.
+    pub fn take_pondered(&mut self, morpion: &Morpion) -> Option<Morpion> {
+        self.try_result();
+        let reply = self.pondered.remove(&morpion.hash())?;
+        self.cancel();
+        self.clear_pondered();
+        Some(reply)
+    }
+
+    /// Aborts the running job without waiting for the worker to stop it:
+    /// the messages of the job that still come are ignored.
+    pub fn cancel(&mut self) {
+        if let Some((_, kind, stop)) = self.current.take() {
+            stop.store(true, Ordering::Relaxed);
+            if let JobKind::Ponder(_) = kind {
+                // The pondering is incomplete, it will have to start over
+                self.clear_pondered();
+            }
+        }
+    }
+
+    /// Cancels the running job and joins the worker thread.
+    pub fn shutdown(&mut self) {
+        self.cancel();
+        // Closing the channel ends the worker loop
+        self.jobs = None;
+        if let Some(thread) = self.thread.take() {
+            thread
+                .join()
+                .unwrap_or_else(|_| println!("AI worker panicked"));
+        }
+    }
+}
+
+impl Drop for AIWorker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}